@@ -1,58 +1,339 @@
-use failure::Error;
 use futures::{
-    future::{self, Either},
-    Future,
+    future::Either,
+    stream::{self, Stream, StreamExt},
 };
-use reqwest::r#async::Client;
+use reqwest::{Client, Url};
 use serde::Deserialize;
+use std::{sync::Arc, time::Instant};
+use tokio::time;
+use tracing::Instrument;
 
-use super::check_status;
-use crate::request::Request;
+use super::{
+    check_status, crawl::Cursor, options::Limits, pages::Prefetch, Config, Fetched, Metrics,
+    Middleware, RateLimiter, RawResponse, RetryPolicy, SendOptions,
+};
+use crate::{
+    error::Error,
+    models::Image,
+    request::{Keyset, KeysetPaginated, Paginated, Request},
+    transport::{AsyncTransport, HttpRequest, HttpResponse},
+};
 
-/// Async adapter powered by asynchronous reqwest's [Client](reqwest::async::Client).
-pub struct AsyncAdapter {
-    client: Client,
+/// Async adapter powered by asynchronous reqwest's [Client](reqwest::Client)
+/// or any other [AsyncTransport].
+///
+/// Returned futures are std futures which require [tokio](https://tokio.rs) runtime
+/// with enabled timer.
+pub struct AsyncAdapter<T = Client> {
+    transport: T,
+    pub(super) config: Config,
 }
 impl AsyncAdapter {
-    /// Creates new async asynchronous with default [Client](reqwest::async::Client).
+    /// Creates new async asynchronous with default [Client](reqwest::Client).
     pub fn new() -> Self {
-        let client = Client::new();
+        Self::from(Client::new())
+    }
+}
+impl<T: AsyncTransport> AsyncAdapter<T> {
+    /// Creates new asynchronous adapter sending requests through `transport`.
+    pub fn with_transport(transport: T) -> Self {
+        let config = Config::new();
 
-        AsyncAdapter { client }
+        AsyncAdapter { transport, config }
+    }
+    /// Set API base URL (default is [DERPIBOORU_API_BASE](crate::request::DERPIBOORU_API_BASE)).
+    pub fn base_url(mut self, base: Url) -> Self {
+        self.config.base = base;
+        self
+    }
+    /// Set retry policy for failed requests (default is [RetryPolicy::none]).
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.config.retry = policy;
+        self
+    }
+    /// Set rate limiter, every attempt to send a request waits for its permit.
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.config.rate_limiter = Some(limiter);
+        self
+    }
+    /// Add middleware, it runs after previously added ones.
+    pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.config.middleware.push(Arc::new(middleware));
+        self
+    }
+    /// Set metrics collector.
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.config.metrics = Some(metrics);
+        self
+    }
+    /// Decode responses leniently: only ids and creation dates are required, unknown fields
+    /// are kept in `extra` maps of models (see [models](crate::models)).
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.config.lenient = lenient;
+        self
+    }
+    /// Set default query parameter, added to requests which don't set it.
+    pub fn default_param<V: ToString>(mut self, key: &str, value: V) -> Self {
+        self.config.set_default(key, value.to_string());
+        self
+    }
+    /// Set default user key.
+    pub fn key(self, key: &str) -> Self {
+        self.default_param("key", key)
+    }
+    /// Set default filter.
+    pub fn filter_id(self, id: u64) -> Self {
+        self.default_param("filter_id", id)
+    }
+    /// Set default number of results on each page.
+    pub fn perpage(self, perpage: u64) -> Self {
+        self.default_param("perpage", perpage)
+    }
+    /// Build request as it would be sent by the adapter: with base URL, default parameters
+    /// and headers set by middleware. Nothing is sent.
+    pub fn prepare<'r, R: Request<'r>>(&self, request: R) -> Result<HttpRequest, Error> {
+        self.config.prepare(&request)
     }
     /// Send a request.
-    pub fn send<R: Request<'static>>(
+    pub async fn send<'r, R: Request<'r>>(&self, request: R) -> Result<R::ResponseValue, Error>
+    where
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        // Not delegated to `send_with`: awaiting a future resolving to `R::ResponseValue`
+        // makes returned future not `Send` for requests borrowing data.
+        let url = self.config.url(&request)?;
+        let fetched = self.fetch(&url, &SendOptions::new()).await;
+        self.config.complete(&url, fetched, self.config.decoder())
+    }
+    /// Send a request with timeout and cancellation set by `options`.
+    pub async fn send_with<'r, R: Request<'r>>(
         &self,
         request: R,
-    ) -> impl Future<Item = R::ResponseValue, Error = Error>
+        options: SendOptions,
+    ) -> Result<R::ResponseValue, Error>
     where
         R::ResponseValue: for<'de> Deserialize<'de>,
     {
-        let url = match request.build() {
-            Ok(url) => url,
-            Err(error) => return Either::B(future::err(error)),
-        };
+        let url = self.config.url(&request)?;
+        let fetched = self.fetch(&url, &options).await;
+        self.config.complete(&url, fetched, self.config.decoder())
+    }
+    /// Send a request and return response as raw JSON, regardless of request's response type.
+    pub async fn send_raw<'r, R: Request<'r>>(&self, request: R) -> Result<RawResponse, Error> {
+        let url = self.config.url(&request)?;
+        let fetched = self.fetch(&url, &SendOptions::new()).await;
+        self.config.complete(&url, fetched, RawResponse::decode)
+    }
+    /// Send request with retries.
+    pub(super) async fn fetch(&self, url: &Url, options: &SendOptions) -> Fetched {
+        let span = self.config.span(url);
+        let started = Instant::now();
+        let limits = options.start(started);
+        let mut attempt = 1;
+
+        let attempts = async {
+            loop {
+                if let Some(limiter) = &self.config.rate_limiter {
+                    time::sleep(limiter.reserve()).await;
+                }
 
-        let fut = self
-            .client
-            .get(url)
-            .send()
-            .from_err()
-            .and_then(|mut response| {
-                if let Some(error) = check_status(response.status()) {
-                    return Either::B(future::err(error));
+                match self.execute(url, &limits).await {
+                    Ok(response) => return Ok(response),
+                    Err(error) => match self.config.retry.delay(attempt, &error) {
+                        Some(delay) => {
+                            tracing::debug!(attempt, ?delay, error = %error, "retrying request");
+                            time::sleep(delay).await;
+                        }
+                        None => return Err(error),
+                    },
                 }
 
-                Either::A(response.json().from_err())
-            });
+                attempt += 1;
+            }
+        }
+        .instrument(span.clone());
+        let response = limits
+            .guard(attempts)
+            .await
+            .map_err(|error| error.with_url(url.clone()));
+
+        Fetched {
+            span,
+            started,
+            attempts: attempt,
+            response,
+        }
+    }
+    /// Send requests with at most `concurrency` of them in flight at once.
+    ///
+    /// Results are yielded in input order together with index of the request.
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use derpiboorust::{AsyncAdapter, Image};
+    ///
+    /// # async fn run() {
+    /// let adapter = AsyncAdapter::new();
+    /// let requests = (1..=100).map(Image::new);
+    /// let mut results = adapter.send_batch(requests, 4);
+    ///
+    /// while let Some((index, result)) = results.next().await {
+    ///     println!("#{}: {:?}", index, result);
+    /// }
+    /// # }
+    /// ```
+    pub fn send_batch<'a, 'r, I, R>(
+        &'a self,
+        requests: I,
+        concurrency: usize,
+    ) -> impl Stream<Item = (usize, Result<R::ResponseValue, Error>)> + 'a
+    where
+        I: IntoIterator<Item = R>,
+        I::IntoIter: 'a,
+        R: Request<'r> + 'a,
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        self.batch(requests, concurrency, false)
+    }
+    /// Same as [send_batch](AsyncAdapter::send_batch), but results are yielded
+    /// in completion order.
+    pub fn send_batch_unordered<'a, 'r, I, R>(
+        &'a self,
+        requests: I,
+        concurrency: usize,
+    ) -> impl Stream<Item = (usize, Result<R::ResponseValue, Error>)> + 'a
+    where
+        I: IntoIterator<Item = R>,
+        I::IntoIter: 'a,
+        R: Request<'r> + 'a,
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        self.batch(requests, concurrency, true)
+    }
+    fn batch<'a, 'r, I, R>(
+        &'a self,
+        requests: I,
+        concurrency: usize,
+        unordered: bool,
+    ) -> impl Stream<Item = (usize, Result<R::ResponseValue, Error>)> + 'a
+    where
+        I: IntoIterator<Item = R>,
+        I::IntoIter: 'a,
+        R: Request<'r> + 'a,
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        let concurrency = concurrency.max(1);
+        let sends = stream::iter(requests.into_iter().enumerate())
+            .map(move |(index, request)| async move { (index, self.send(request).await) });
+
+        if unordered {
+            Either::Left(sends.buffer_unordered(concurrency))
+        } else {
+            Either::Right(sends.buffered(concurrency))
+        }
+    }
+    /// Stream items of all pages of `request`, fetching up to `prefetch` pages ahead
+    /// of the one being consumed.
+    ///
+    /// Pages are fetched starting from the first one (page set on request is ignored), until
+    /// a page is empty or all items reported by response are fetched, pages past the total
+    /// aren't requested. Fetches are driven whenever the stream is polled, also while items
+    /// of a fetched page are yielded. Failed request is yielded as error and ends the stream.
+    /// Dropping the stream cancels pending fetches.
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use derpiboorust::{AsyncAdapter, Search};
+    ///
+    /// # async fn run() {
+    /// let adapter = AsyncAdapter::new();
+    /// let images = adapter.pages(Search::new("safe, luna"), 2).take(100);
+    /// futures::pin_mut!(images);
+    ///
+    /// while let Some(image) = images.next().await {
+    ///     println!("{}", image.unwrap().id);
+    /// }
+    /// # }
+    /// ```
+    pub fn pages<'a, 'r, R>(
+        &'a self,
+        request: R,
+        prefetch: usize,
+    ) -> impl Stream<Item = Result<R::Item, Error>> + 'a
+    where
+        R: Paginated<'r> + 'a,
+        R::Item: 'a,
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        // Response is decoded outside of awaiting, like in `send`
+        let mut pages = Prefetch::new(prefetch, move |page| {
+            let url = self.config.url(&request.clone().with_page(page));
+            async move {
+                let url = url?;
+                let fetched = self.fetch(&url, &SendOptions::new()).await;
+                let response: R::ResponseValue =
+                    self.config.complete(&url, fetched, self.config.decoder())?;
+
+                Ok((R::total(&response), R::items(response)))
+            }
+        });
+
+        stream::poll_fn(move |cx| pages.poll_next(cx))
+    }
+    /// Stream images of `request` walked by `keyset`, see [Crawl](super::Crawl).
+    pub fn crawl<'a, 'r, R>(
+        &'a self,
+        request: R,
+        keyset: Keyset,
+    ) -> impl Stream<Item = Result<Image, Error>> + 'a
+    where
+        R: KeysetPaginated<'r> + 'a,
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        stream::unfold(Cursor::new(keyset), move |mut cursor| {
+            let page = if cursor.done {
+                None
+            } else {
+                Some(cursor.request(&request))
+            };
+
+            async move {
+                let images = match self.send(page?).await {
+                    Ok(response) => cursor
+                        .advance(R::items(response))
+                        .into_iter()
+                        .map(Ok)
+                        .collect(),
+                    Err(error) => {
+                        cursor.done = true;
+                        vec![Err(error)]
+                    }
+                };
+
+                Some((stream::iter(images), cursor))
+            }
+        })
+        .flatten()
+    }
+    async fn execute(&self, url: &Url, limits: &Limits) -> Result<HttpResponse, Error> {
+        let mut request = HttpRequest::get(url.clone());
+        request.timeout = limits.remaining()?;
+        let (chain, response) = self.config.before_send(&mut request)?;
+        let mut response = match response {
+            Some(response) => response,
+            None => self.transport.execute(request.clone()).await?,
+        };
+        self.config.after_receive(chain, &request, &mut response)?;
+
+        if let Some(error) = check_status(&response, url) {
+            return Err(error);
+        }
 
-        Either::A(fut)
+        Ok(response)
     }
 }
 
 impl From<Client> for AsyncAdapter {
     fn from(client: Client) -> Self {
-        AsyncAdapter { client }
+        Self::with_transport(client)
     }
 }
 
@@ -61,3 +342,305 @@ impl Default for AsyncAdapter {
         Self::new()
     }
 }
+
+#[cfg(test)]
+#[tokio::test]
+async fn retry() {
+    use crate::{
+        request::Image,
+        transport::{HttpResponse, MemoryTransport},
+        ErrorKind,
+    };
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    let transport = MemoryTransport::new();
+    let url = Image::new(1).build().unwrap();
+    transport.respond(url.clone(), HttpResponse::new(StatusCode::BAD_GATEWAY, ""));
+    transport.respond(url, HttpResponse::new(StatusCode::NOT_FOUND, ""));
+
+    let policy = RetryPolicy::new().initial_delay(Duration::from_millis(1));
+    let adapter = AsyncAdapter::with_transport(transport.clone()).retry(policy);
+    let error = adapter.send(Image::new(1)).await.unwrap_err();
+
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+    assert_eq!(transport.requests().len(), 2);
+}
+
+#[test]
+fn send_is_send() {
+    fn assert_send<F: Send>(_: F) {}
+    let q = String::from("safe");
+    let adapter = AsyncAdapter::new();
+    assert_send(adapter.send(crate::request::Search::new(&q)));
+    assert_send(adapter.send_with(crate::request::Search::new(&q), SendOptions::new()));
+    assert_send(adapter.pages(crate::request::Search::new(&q), 1));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn batch() {
+    use crate::{
+        request::Image,
+        transport::{HttpResponse, MemoryTransport},
+    };
+    use reqwest::StatusCode;
+
+    let transport = MemoryTransport::new();
+    for id in 1..=5 {
+        let url = Image::new(id).build().unwrap();
+        transport.respond(url, HttpResponse::new(StatusCode::NOT_FOUND, ""));
+    }
+
+    let adapter = AsyncAdapter::with_transport(transport.clone());
+    let results: Vec<_> = adapter
+        .send_batch((1..=6).map(Image::new), 2)
+        .collect()
+        .await;
+
+    let indices: Vec<_> = results.iter().map(|(index, _)| *index).collect();
+    assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+    assert_eq!(
+        results[0].1.as_ref().unwrap_err().status(),
+        Some(StatusCode::NOT_FOUND)
+    );
+    assert_eq!(results[5].1.as_ref().unwrap_err().status(), None);
+
+    let unordered = adapter
+        .send_batch_unordered((1..=6).map(Image::new), 3)
+        .count()
+        .await;
+    assert_eq!(unordered, 6);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn raw() {
+    use crate::{
+        request::Image,
+        transport::{HttpResponse, MemoryTransport},
+    };
+    use reqwest::StatusCode;
+
+    let transport = MemoryTransport::new();
+    let url = Image::new(1).build().unwrap();
+    let body = r#"{"id": 1, "new_field": [1, 2, 3]}"#;
+    transport.respond(url, HttpResponse::new(StatusCode::OK, body));
+
+    let adapter = AsyncAdapter::with_transport(transport);
+    let response = adapter.send_raw(Image::new(1)).await.unwrap();
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["new_field"][2], 3);
+    assert!(adapter.send(Image::new(1)).await.is_err());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn middleware() {
+    use crate::{
+        request::Image,
+        transport::{HttpResponse, MemoryTransport},
+    };
+    use reqwest::StatusCode;
+
+    struct NotFound;
+    impl Middleware for NotFound {
+        fn before_send(&self, _request: &mut HttpRequest) -> Result<Option<HttpResponse>, Error> {
+            Ok(Some(HttpResponse::new(StatusCode::NOT_FOUND, "")))
+        }
+    }
+
+    let transport = MemoryTransport::new();
+    let adapter = AsyncAdapter::with_transport(transport.clone()).middleware(NotFound);
+    let error = adapter.send(Image::new(1)).await.unwrap_err();
+
+    assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+    assert!(transport.requests().is_empty());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn timeout_and_cancel() {
+    use crate::{
+        adapter::CancellationToken,
+        request::Image,
+        transport::{HttpResponse, ResponseFuture},
+        ErrorKind,
+    };
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    struct Slow;
+    impl AsyncTransport for Slow {
+        fn execute(&self, _request: HttpRequest) -> ResponseFuture<'_> {
+            Box::pin(async {
+                time::sleep(Duration::from_secs(10)).await;
+                Ok(HttpResponse::new(StatusCode::OK, "{}"))
+            })
+        }
+    }
+
+    let adapter = AsyncAdapter::with_transport(Slow);
+    let options = SendOptions::new().timeout(Duration::from_millis(10));
+    let error = adapter.send_with(Image::new(1), options).await.unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Timeout);
+    assert_eq!(error.url(), Some(&Image::new(1).build().unwrap()));
+
+    let token = CancellationToken::new();
+    let options = SendOptions::new().cancel(token.clone());
+    let send = adapter.send_with(Image::new(1), options);
+    let cancel = async {
+        time::sleep(Duration::from_millis(10)).await;
+        token.cancel();
+    };
+    let (result, _) = futures::join!(send, cancel);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::Cancelled);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn pages() {
+    use crate::{
+        models::image::sample,
+        request::{Images, Search},
+        transport::{HttpResponse, MemoryTransport},
+    };
+    use reqwest::StatusCode;
+    use serde_json::json;
+
+    let transport = MemoryTransport::new();
+    for (page, ids) in [(1, vec![1, 2]), (2, vec![3])] {
+        let url = Search::new("safe").page(page).build().unwrap();
+        let images: Vec<_> = ids.into_iter().map(sample).collect();
+        let body = json!({ "search": images, "total": 3 }).to_string();
+        transport.respond(url, HttpResponse::new(StatusCode::OK, body));
+    }
+    let url = Images::new().page(1).build().unwrap();
+    transport.respond(url, HttpResponse::new(StatusCode::OK, r#"{"images":[]}"#));
+
+    let adapter = AsyncAdapter::with_transport(transport.clone());
+    let ids: Vec<_> = adapter
+        .pages(Search::new("safe"), 3)
+        .map(|image| image.unwrap().id)
+        .collect()
+        .await;
+    assert_eq!(ids, vec![1, 2, 3]);
+
+    let first: Vec<_> = adapter
+        .pages(Search::new("safe"), 0)
+        .take(1)
+        .collect()
+        .await;
+    assert_eq!(first.len(), 1);
+
+    let requests = transport.requests().len();
+    let images = adapter.pages(Images::new(), 0).count().await;
+    assert_eq!(images, 0);
+    assert_eq!(transport.requests().len(), requests + 1);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn prefetch() {
+    use crate::{
+        models::image::sample,
+        request::Search,
+        transport::{HttpResponse, MemoryTransport, ResponseFuture},
+    };
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use std::{sync::Mutex, time::Duration};
+
+    /// Transport answering after a delay, logging when requests of pages start and finish.
+    struct Delayed {
+        responses: MemoryTransport,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+    impl AsyncTransport for Delayed {
+        fn execute(&self, request: HttpRequest) -> ResponseFuture<'_> {
+            Box::pin(async move {
+                let (_, page) = request
+                    .url
+                    .query_pairs()
+                    .find(|(key, _)| key == "page")
+                    .unwrap();
+                self.log.lock().unwrap().push(format!("start {}", page));
+                time::sleep(Duration::from_millis(50)).await;
+                self.log.lock().unwrap().push(format!("finish {}", page));
+
+                AsyncTransport::execute(&self.responses, request).await
+            })
+        }
+    }
+
+    let responses = MemoryTransport::new();
+    for page in 1..=4 {
+        let url = Search::new("safe").page(page).build().unwrap();
+        let images: Vec<_> = (page * 2 - 1..=page * 2).map(sample).collect();
+        let body = json!({ "search": images, "total": 6 }).to_string();
+        responses.respond(url, HttpResponse::new(StatusCode::OK, body));
+    }
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let transport = Delayed {
+        responses,
+        log: log.clone(),
+    };
+
+    let adapter = AsyncAdapter::with_transport(transport);
+    let images = adapter.pages(Search::new("safe"), 1);
+    futures::pin_mut!(images);
+    while let Some(image) = images.next().await {
+        let id = image.unwrap().id;
+        log.lock().unwrap().push(format!("item {}", id));
+        time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let log = log.lock().unwrap();
+    let position = |event: &str| log.iter().position(|logged| logged == event);
+    assert_eq!(
+        log.iter().filter(|event| event.starts_with("item")).count(),
+        6
+    );
+    // Next page loads while items of the current one are consumed
+    assert!(position("start 2") < position("item 1"));
+    assert!(position("finish 2") < position("item 2"));
+    assert!(position("start 3") < position("item 4"));
+    assert!(position("finish 3") < position("item 5"));
+    // Page after `total` is reached isn't requested
+    assert_eq!(position("start 4"), None);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn crawl() {
+    use crate::{
+        models::image::sample,
+        request::Images,
+        transport::{HttpResponse, MemoryTransport},
+    };
+    use reqwest::StatusCode;
+    use serde_json::json;
+
+    let transport = MemoryTransport::new();
+    let pages: [(Option<&str>, &[u64]); 3] = [(None, &[5, 4]), (Some("4"), &[2]), (Some("2"), &[])];
+    for (before, ids) in pages {
+        let url = Images::new()
+            .keyset(Keyset::Id, before, false)
+            .build()
+            .unwrap();
+        let images: Vec<_> = ids.iter().map(|id| sample(*id)).collect();
+        let body = json!({ "images": images }).to_string();
+        transport.respond(url, HttpResponse::new(StatusCode::OK, body));
+    }
+
+    let adapter = AsyncAdapter::with_transport(transport.clone());
+    let ids: Vec<_> = adapter
+        .crawl(Images::new().page(2), Keyset::Id)
+        .map(|image| image.unwrap().id)
+        .collect()
+        .await;
+
+    assert_eq!(ids, vec![5, 4, 2]);
+    assert_eq!(transport.requests().len(), 3);
+}
@@ -0,0 +1,96 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+use tokio::sync::Notify;
+
+/// Token for cooperative cancellation of sends.
+///
+/// Token is cheaply cloneable and clones share cancellation state, so one token can be
+/// passed to many sends (see [SendOptions](crate::adapter::SendOptions)) and cancelled from
+/// another thread or task. Cancelled sends fail with [Cancelled](crate::ErrorKind::Cancelled)
+/// error: async sends are aborted immediately, sync sends stop waiting (for rate limiter or
+/// before retry) immediately and don't start new attempts, but an attempt in progress
+/// is finished.
+/// ```
+/// use derpiboorust::{adapter::{CancellationToken, SendOptions}, Image, SyncAdapter};
+///
+/// let token = CancellationToken::new();
+/// let options = SendOptions::new().cancel(token.clone());
+/// token.cancel();
+///
+/// let adapter = SyncAdapter::new();
+/// assert!(adapter.send_with(Image::new(1), options).is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: Mutex<bool>,
+    condvar: Condvar,
+    notify: Notify,
+}
+impl CancellationToken {
+    /// Create new token.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Cancel all sends using this token.
+    pub fn cancel(&self) {
+        *self.lock() = true;
+        self.inner.condvar.notify_all();
+        self.inner.notify.notify_waiters();
+    }
+    /// Whether token is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        *self.lock()
+    }
+    /// Block current thread for `duration` or until token is cancelled.
+    pub(crate) fn wait(&self, duration: Duration) {
+        let cancelled = self.lock();
+        let _ = self
+            .inner
+            .condvar
+            .wait_timeout_while(cancelled, duration, |cancelled| !*cancelled);
+    }
+    /// Wait until token is cancelled.
+    pub(crate) async fn cancelled(&self) {
+        loop {
+            // Created before the check, so cancellation between them is not missed
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+    fn lock(&self) -> std::sync::MutexGuard<'_, bool> {
+        self.inner
+            .cancelled
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+#[test]
+fn wait() {
+    use std::{thread, time::Instant};
+
+    let token = CancellationToken::new();
+    let started = Instant::now();
+    token.wait(Duration::from_millis(10));
+    assert!(started.elapsed() >= Duration::from_millis(10));
+
+    let shared = token.clone();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        shared.cancel();
+    });
+    token.wait(Duration::from_secs(10));
+    canceller.join().unwrap();
+
+    assert!(token.is_cancelled());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,246 @@
+use reqwest::{
+    header::{ETAG, LAST_MODIFIED},
+    Method, StatusCode, Url,
+};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use super::Middleware;
+use crate::{
+    error::Error,
+    transport::{set_validators, HttpRequest, HttpResponse},
+};
+
+/// Middleware which makes repeated requests conditional.
+///
+/// Validators (`ETag`, `Last-Modified`) of successful responses are remembered per URL
+/// and sent as `If-None-Match` and `If-Modified-Since` headers with next requests to the same
+/// URL. When server responds with `304 Not Modified`, remembered response is returned, or,
+/// with [report_not_modified](ConditionalGet::report_not_modified), `send` fails with
+/// [NotModified](crate::ErrorKind::NotModified) error.
+///
+/// At most [capacity](ConditionalGet::capacity) URLs are remembered, least recently used
+/// ones are forgotten. Responses without validators aren't remembered.
+///
+/// For persistent caching with revalidation see [CachingTransport](crate::cache::CachingTransport).
+/// ```
+/// use derpiboorust::{ConditionalGet, SyncAdapter};
+///
+/// let adapter = SyncAdapter::new().middleware(ConditionalGet::new().capacity(100));
+/// ```
+#[derive(Debug)]
+pub struct ConditionalGet {
+    report: bool,
+    capacity: usize,
+    responses: Mutex<Responses>,
+}
+impl ConditionalGet {
+    /// Create new middleware returning remembered response on `304 Not Modified`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Don't replace `304 Not Modified` responses, so they are reported as errors.
+    /// Only validators of responses are remembered then.
+    pub fn report_not_modified(mut self) -> Self {
+        self.report = true;
+        self
+    }
+    /// Maximum number of remembered URLs (default is 1024).
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+    fn responses(&self) -> MutexGuard<'_, Responses> {
+        self.responses
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl Default for ConditionalGet {
+    fn default() -> Self {
+        ConditionalGet {
+            report: false,
+            capacity: 1024,
+            responses: Mutex::new(Responses::default()),
+        }
+    }
+}
+
+/// Remembered responses with last use time, evicted when least recently used.
+#[derive(Debug, Default)]
+struct Responses {
+    entries: HashMap<String, (u64, HttpResponse)>,
+    clock: u64,
+}
+impl Responses {
+    fn get(&mut self, url: &Url) -> Option<&HttpResponse> {
+        self.clock += 1;
+        let (used, response) = self.entries.get_mut(&entry_key(url))?;
+        *used = self.clock;
+
+        Some(response)
+    }
+    fn insert(&mut self, url: &Url, response: HttpResponse, capacity: usize) {
+        let key = entry_key(url);
+        if !self.entries.contains_key(&key) && self.entries.len() >= capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.clock += 1;
+        self.entries.insert(key, (self.clock, response));
+    }
+}
+
+/// URL with user key replaced by its hash, so the key itself isn't kept in memory.
+fn entry_key(url: &Url) -> String {
+    if url.query_pairs().all(|(key, _)| key != "key") {
+        return url.to_string();
+    }
+
+    let mut url = url.clone();
+    let pairs: Vec<_> = url
+        .query_pairs()
+        .map(|(key, value)| {
+            let value = if key == "key" {
+                let mut hasher = DefaultHasher::new();
+                value.hash(&mut hasher);
+                format!("{:016x}", hasher.finish())
+            } else {
+                value.into_owned()
+            };
+            (key.into_owned(), value)
+        })
+        .collect();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+
+    url.into()
+}
+
+impl Middleware for ConditionalGet {
+    fn before_send(&self, request: &mut HttpRequest) -> Result<Option<HttpResponse>, Error> {
+        if request.method == Method::GET {
+            if let Some(previous) = self.responses().get(&request.url) {
+                set_validators(request, previous);
+            }
+        }
+
+        Ok(None)
+    }
+    fn after_receive(
+        &self,
+        request: &HttpRequest,
+        response: &mut HttpResponse,
+        _elapsed: Duration,
+    ) -> Result<(), Error> {
+        if request.method != Method::GET {
+            return Ok(());
+        }
+
+        let mut responses = self.responses();
+        if response.status == StatusCode::NOT_MODIFIED {
+            if let (false, Some(previous)) = (self.report, responses.get(&request.url)) {
+                *response = previous.clone();
+            }
+        } else if response.status.is_success() {
+            let mut validators = HttpResponse::new(response.status, Vec::new());
+            for name in &[ETAG, LAST_MODIFIED] {
+                if let Some(value) = response.headers.get(name) {
+                    validators.headers.insert(name, value.clone());
+                }
+            }
+            if validators.headers.is_empty() {
+                return Ok(());
+            }
+
+            // Only validators are needed to report
+            let remembered = if self.report {
+                validators
+            } else {
+                response.clone()
+            };
+            responses.insert(&request.url, remembered, self.capacity);
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn not_modified() {
+    use crate::{
+        adapter::SyncAdapter,
+        request::{Lists, Request},
+        transport::MemoryTransport,
+        ErrorKind,
+    };
+    use reqwest::header::{HeaderValue, IF_MODIFIED_SINCE, LAST_MODIFIED};
+
+    let url = Lists::new().build().unwrap();
+    let body = r#"{"top_scoring":[],"top_commented":[],"all_time_top_scoring":[]}"#;
+    let last_modified = HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT");
+    let mut response = HttpResponse::new(StatusCode::OK, body);
+    response
+        .headers
+        .insert(LAST_MODIFIED, last_modified.clone());
+
+    let transport = MemoryTransport::new();
+    transport.respond(url.clone(), response);
+    transport.respond(url, HttpResponse::new(StatusCode::NOT_MODIFIED, ""));
+
+    let adapter = SyncAdapter::with_transport(transport.clone()).middleware(ConditionalGet::new());
+    adapter.send(Lists::new()).unwrap();
+    adapter.send(Lists::new()).unwrap();
+    assert_eq!(
+        transport.requests()[1].headers[IF_MODIFIED_SINCE],
+        last_modified
+    );
+
+    let adapter = SyncAdapter::with_transport(transport)
+        .middleware(ConditionalGet::new().report_not_modified());
+    let error = adapter.send(Lists::new()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotModified);
+}
+
+#[test]
+fn capacity() {
+    use reqwest::header::HeaderValue;
+
+    let conditional = ConditionalGet::new().report_not_modified().capacity(2);
+    let url = |id: u32| {
+        let url = format!("https://derpibooru.org/{}.json?key=secret", id);
+        Url::parse(&url).unwrap()
+    };
+    let mut response = HttpResponse::new(StatusCode::OK, "{}");
+    response
+        .headers
+        .insert(ETAG, HeaderValue::from_static("\"abc\""));
+
+    for id in 1..=3 {
+        let request = HttpRequest::get(url(id));
+        let mut response = response.clone();
+        conditional
+            .after_receive(&request, &mut response, Duration::from_secs(0))
+            .unwrap();
+        // Keep the first URL recently used
+        conditional.responses().get(&url(1));
+    }
+
+    let mut responses = conditional.responses();
+    assert_eq!(responses.entries.len(), 2);
+    assert!(responses.get(&url(1)).is_some());
+    assert!(responses.get(&url(2)).is_none());
+    assert!(responses.get(&url(3)).unwrap().body.is_empty());
+    assert!(responses.entries.keys().all(|key| !key.contains("secret")));
+}
@@ -0,0 +1,248 @@
+use serde::Deserialize;
+use std::{
+    collections::{HashSet, VecDeque},
+    marker::PhantomData,
+};
+
+use super::SyncAdapter;
+use crate::{
+    error::Error,
+    models::Image,
+    request::{Keyset, KeysetPaginated},
+    transport::Transport,
+};
+
+/// Position of keyset crawl.
+///
+/// Pages are requested by keyset value of the last seen image. Creation times aren't unique,
+/// so pages by [CreatedAt](Keyset::CreatedAt) include images created at the same time as
+/// the last seen one, and images already seen are skipped. While the keyset value stays
+/// the same (more images created at the same time than fit on a page), following pages
+/// of the same request are requested by page number.
+pub(crate) struct Cursor {
+    keyset: Keyset,
+    last: Option<String>,
+    /// Page of the request bounded by `last`.
+    page: u64,
+    /// Seen images having keyset value equal to `last`.
+    boundary: HashSet<u64>,
+    pub(crate) done: bool,
+}
+impl Cursor {
+    pub(crate) fn new(keyset: Keyset) -> Self {
+        Cursor {
+            keyset,
+            last: None,
+            page: 1,
+            boundary: HashSet::new(),
+            done: false,
+        }
+    }
+    /// Request of the next page.
+    pub(crate) fn request<'r, R: KeysetPaginated<'r>>(&self, request: &R) -> R {
+        let inclusive = self.keyset == Keyset::CreatedAt;
+        let request = request
+            .clone()
+            .keyset(self.keyset, self.last.as_deref(), inclusive);
+
+        if self.page > 1 {
+            return request.with_page(self.page);
+        }
+        request
+    }
+    /// Move past fetched page, returns images not seen before.
+    pub(crate) fn advance(&mut self, images: Vec<Image>) -> Vec<Image> {
+        if images.is_empty() {
+            self.done = true;
+            return images;
+        }
+
+        let images: Vec<_> = images
+            .into_iter()
+            .filter(|image| !self.boundary.contains(&image.id))
+            .collect();
+        let last = match images.last() {
+            Some(image) => self.keyset.value(image),
+            // Whole page is at the boundary, the rest is on the next one
+            None => {
+                self.page += 1;
+                return images;
+            }
+        };
+
+        if self.last.as_ref() == Some(&last) {
+            self.page += 1;
+        } else {
+            self.boundary.clear();
+            self.page = 1;
+        }
+        for image in &images {
+            if self.keyset.value(image) == last {
+                self.boundary.insert(image.id);
+            }
+        }
+        self.last = Some(last);
+
+        images
+    }
+}
+
+/// Iterator over images walked by keyset, created by [SyncAdapter::crawl].
+///
+/// Unlike [Pages](super::Pages), pages are requested by keyset value of the last seen image
+/// (e.g. "id less than the last seen one") instead of page numbers, so images uploaded
+/// or removed during a long crawl don't make it skip or repeat images. Images are walked
+/// from the newest to the oldest, constraint, order, upper bounds and random sorting set
+/// on request are replaced. Lower bounds (`gt`, `gte`) are kept and apply to the keyset
+/// field. Failed request is yielded as error and ends iteration.
+/// ```no_run
+/// use derpiboorust::{Images, Keyset, SyncAdapter};
+///
+/// let adapter = SyncAdapter::new();
+/// for image in adapter.crawl(Images::new(), Keyset::Id) {
+///     println!("{}", image.unwrap().id);
+/// }
+/// ```
+pub struct Crawl<'s, 'r, T, R> {
+    adapter: &'s SyncAdapter<T>,
+    request: R,
+    cursor: Cursor,
+    images: VecDeque<Image>,
+    lifetime: PhantomData<&'r ()>,
+}
+impl<'s, 'r, T, R> Crawl<'s, 'r, T, R> {
+    pub(crate) fn new(adapter: &'s SyncAdapter<T>, request: R, keyset: Keyset) -> Self {
+        Crawl {
+            adapter,
+            request,
+            cursor: Cursor::new(keyset),
+            images: VecDeque::new(),
+            lifetime: PhantomData,
+        }
+    }
+}
+
+impl<'s, 'r, T, R> Iterator for Crawl<'s, 'r, T, R>
+where
+    T: Transport,
+    R: KeysetPaginated<'r>,
+    R::ResponseValue: for<'de> Deserialize<'de>,
+{
+    type Item = Result<Image, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(image) = self.images.pop_front() {
+                return Some(Ok(image));
+            }
+            if self.cursor.done {
+                return None;
+            }
+
+            match self.adapter.send(self.cursor.request(&self.request)) {
+                Ok(response) => {
+                    let images = self.cursor.advance(R::items(response));
+                    self.images.extend(images);
+                }
+                Err(error) => {
+                    self.cursor.done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn cursor() {
+    use crate::{models::image::sample, request::Request, Images};
+
+    let images = |ids: &[(u64, &str)]| -> Vec<Image> {
+        ids.iter()
+            .map(|(id, created_at)| {
+                let mut image = sample(*id);
+                image["created_at"] = (*created_at).into();
+                serde_json::from_value(image).unwrap()
+            })
+            .collect()
+    };
+    let ids = |images: Vec<Image>| -> Vec<u64> { images.iter().map(|image| image.id).collect() };
+    let query = |cursor: &Cursor| -> String {
+        let url = cursor
+            .request(&Images::new().page(3).random())
+            .build()
+            .unwrap();
+        url.query().unwrap().to_owned()
+    };
+
+    let mut cursor = Cursor::new(Keyset::Id);
+    assert_eq!(query(&cursor), "constraint=id&order=d");
+    let seen = cursor.advance(images(&[
+        (9, "2020-01-01T00:00:00Z"),
+        (7, "2020-01-01T00:00:00Z"),
+    ]));
+    assert_eq!(ids(seen), vec![9, 7]);
+    assert_eq!(query(&cursor), "constraint=id&order=d&lt=7");
+    assert!(cursor.advance(Vec::new()).is_empty());
+    assert!(cursor.done);
+
+    let mut cursor = Cursor::new(Keyset::CreatedAt);
+    let seen = cursor.advance(images(&[
+        (9, "2020-01-03T00:00:00Z"),
+        (8, "2020-01-02T00:00:00Z"),
+    ]));
+    assert_eq!(ids(seen), vec![9, 8]);
+    assert_eq!(
+        query(&cursor),
+        "constraint=created_at&order=d&lte=2020-01-02T00%3A00%3A00Z"
+    );
+    let seen = cursor.advance(images(&[
+        (8, "2020-01-02T00:00:00Z"),
+        (7, "2020-01-02T00:00:00Z"),
+    ]));
+    assert_eq!(ids(seen), vec![7]);
+    assert_eq!(
+        query(&cursor),
+        "constraint=created_at&order=d&lte=2020-01-02T00%3A00%3A00Z&page=2"
+    );
+    let seen = cursor.advance(images(&[
+        (5, "2020-01-02T00:00:00Z"),
+        (4, "2020-01-01T00:00:00Z"),
+    ]));
+    assert_eq!(ids(seen), vec![5, 4]);
+    assert_eq!(
+        query(&cursor),
+        "constraint=created_at&order=d&lte=2020-01-01T00%3A00%3A00Z"
+    );
+    assert!(cursor
+        .advance(images(&[(4, "2020-01-01T00:00:00Z")]))
+        .is_empty());
+    assert!(!cursor.done);
+    assert!(cursor.advance(Vec::new()).is_empty());
+    assert!(cursor.done);
+}
+
+#[test]
+fn full_boundary_page() {
+    use crate::{models::image::sample, request::Request, Bound, Images};
+
+    let page = |ids: &[u64]| -> Vec<Image> {
+        ids.iter()
+            .map(|id| serde_json::from_value(sample(*id)).unwrap())
+            .collect()
+    };
+    let request = Images::new().constraint_bound(Bound::Gt("2019-01-01"));
+    let query = |cursor: &Cursor| cursor.request(&request).build().unwrap();
+
+    let mut cursor = Cursor::new(Keyset::CreatedAt);
+    assert_eq!(cursor.advance(page(&[9, 8])).len(), 2);
+    // Seen images at the same time fill the whole page
+    assert!(cursor.advance(page(&[9, 8])).is_empty());
+    assert!(!cursor.done);
+
+    let url = query(&cursor);
+    let pairs: Vec<_> = url.query_pairs().collect();
+    assert!(pairs.contains(&("page".into(), "2".into())));
+    assert!(pairs.contains(&("gt".into(), "2019-01-01".into())));
+    assert_eq!(cursor.advance(page(&[7])).len(), 1);
+}
@@ -0,0 +1,243 @@
+use serde::{
+    de::{self, value::StringDeserializer, DeserializeSeed, Deserializer, MapAccess, SeqAccess},
+    forward_to_deserialize_any,
+};
+use serde_json::{Error, Map, Value};
+use std::vec;
+
+/// Fields which are required by lenient decoding too.
+const ESSENTIAL: &[&str] = &["id", "created_at", "updated_at", "first_seen_at"];
+/// Field of models keeping fields unknown to them.
+const EXTRA: &str = "extra";
+
+/// Deserializer of JSON value giving default values to missing non-essential fields
+/// of structs and keeping fields unknown to a struct in its `extra` field.
+pub(super) struct Lenient(pub(super) Value);
+
+impl<'de> Deserializer<'de> for Lenient {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Array(values) => visitor.visit_seq(Elements(values.into_iter())),
+            Value::Object(object) => {
+                let entries = object
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value)))
+                    .collect::<Vec<_>>();
+                visitor.visit_map(Entries::new(entries))
+            }
+            value => value.deserialize_any(visitor),
+        }
+    }
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let object = match self.0 {
+            Value::Object(object) => object,
+            value => return value.deserialize_any(visitor),
+        };
+
+        let mut entries = Vec::new();
+        let mut unknown = Map::new();
+        for (key, value) in object {
+            if fields.contains(&key.as_str()) {
+                entries.push((key, Some(value)));
+            } else {
+                unknown.insert(key, value);
+            }
+        }
+        if fields.contains(&EXTRA) && entries.iter().all(|(key, _)| key != EXTRA) {
+            entries.push((EXTRA.to_owned(), Some(Value::Object(unknown))));
+        }
+
+        // Missing essential fields are left to fail decoding
+        for field in fields {
+            if !ESSENTIAL.contains(field) && entries.iter().all(|(key, _)| key != field) {
+                entries.push(((*field).to_owned(), None));
+            }
+        }
+
+        visitor.visit_map(Entries::new(entries))
+    }
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+/// Deserializer of missing field, giving default value of the field type.
+struct Missing;
+
+/// Implement deserializer methods visiting fixed values.
+macro_rules! visit_defaults {
+    ($($method:ident => $visit:ident($($value:expr)?),)*) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit($($value)?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Missing {
+    type Error = Error;
+
+    visit_defaults! {
+        deserialize_any => visit_unit(),
+        deserialize_bool => visit_bool(false),
+        deserialize_i8 => visit_i64(0),
+        deserialize_i16 => visit_i64(0),
+        deserialize_i32 => visit_i64(0),
+        deserialize_i64 => visit_i64(0),
+        deserialize_u8 => visit_u64(0),
+        deserialize_u16 => visit_u64(0),
+        deserialize_u32 => visit_u64(0),
+        deserialize_u64 => visit_u64(0),
+        deserialize_f32 => visit_f64(0.0),
+        deserialize_f64 => visit_f64(0.0),
+        deserialize_str => visit_str(""),
+        deserialize_string => visit_str(""),
+        deserialize_option => visit_none(),
+        deserialize_unit => visit_unit(),
+        deserialize_seq => visit_seq(Elements(Vec::new().into_iter())),
+        deserialize_map => visit_map(Entries::new(Vec::new())),
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        Lenient(Value::Object(Map::new())).deserialize_struct(name, fields, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char bytes byte_buf unit_struct tuple tuple_struct enum identifier ignored_any
+    }
+}
+
+/// Elements of JSON array.
+struct Elements(vec::IntoIter<Value>);
+
+impl<'de> SeqAccess<'de> for Elements {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(Lenient(value)))
+            .transpose()
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+/// Entries of JSON object, `None` values are missing fields.
+struct Entries {
+    entries: vec::IntoIter<(String, Option<Value>)>,
+    value: Option<Option<Value>>,
+}
+impl Entries {
+    fn new(entries: Vec<(String, Option<Value>)>) -> Self {
+        Entries {
+            entries: entries.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for Entries {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let (key, value) = match self.entries.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        self.value = Some(value);
+
+        seed.deserialize(StringDeserializer::new(key)).map(Some)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        match self.value.take() {
+            Some(Some(value)) => seed.deserialize(Lenient(value)),
+            Some(None) => seed.deserialize(Missing),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+#[test]
+fn defaults_and_extra() {
+    use crate::models::{Gallery, Image};
+    use serde::Deserialize;
+
+    let image = Image::deserialize(Lenient(serde_json::json!({
+        "id": 1,
+        "created_at": "2020-01-01T00:00:00Z",
+        "updated_at": "2020-01-01T00:00:00Z",
+        "first_seen_at": "2020-01-01T00:00:00Z",
+        "score": 10,
+        "tag_ids": [1, "2"],
+        "representations": {"tall": "tall.png", "webm": "full.webm"},
+        "animated": true
+    })))
+    .unwrap();
+
+    assert_eq!((image.id, image.score, image.width), (1, 10, 0));
+    assert_eq!(image.tag_ids.len(), 2);
+    assert!(image.tags.is_empty() && image.uploader_id.is_none());
+    assert_eq!(image.representations.tall, "tall.png");
+    assert!(image.representations.full.is_empty());
+    assert_eq!(image.representations.extra["webm"], "full.webm");
+    assert_eq!(image.extra["animated"], true);
+    assert!(image.extra.get("score").is_none());
+
+    let missing_date = serde_json::json!({"id": 1, "updated_at": "2020-01-01T00:00:00Z"});
+    let error = Gallery::deserialize(Lenient(missing_date)).unwrap_err();
+    assert_eq!(error.to_string(), "missing field `created_at`");
+}
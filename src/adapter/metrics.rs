@@ -0,0 +1,163 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Upper bounds of latency histogram buckets, in milliseconds.
+const LATENCY_BUCKETS_MS: [u64; 10] = [50, 100, 250, 500, 1000, 2500, 5000, 10_000, 30_000, 60_000];
+
+/// Collector of per-endpoint API call metrics.
+///
+/// Metrics is cheaply cloneable and clones share collected data, so one collector can be
+/// attached to several adapters and kept for taking snapshots.
+/// ```
+/// use derpiboorust::{Metrics, SyncAdapter};
+///
+/// let metrics = Metrics::new();
+/// let adapter = SyncAdapter::new().metrics(metrics.clone());
+/// // ...
+/// for (endpoint, endpoint_metrics) in metrics.snapshot().endpoints {
+///     println!("{}: {} calls", endpoint, endpoint_metrics.calls);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    endpoints: Arc<Mutex<BTreeMap<String, EndpointMetrics>>>,
+}
+impl Metrics {
+    /// Create new empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Copy of collected metrics.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let endpoints = self
+            .endpoints
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .clone();
+
+        MetricsSnapshot { endpoints }
+    }
+    /// Remove collected metrics.
+    pub fn reset(&self) {
+        self.endpoints
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .clear();
+    }
+    pub(crate) fn record(&self, endpoint: &str, duration: Duration, attempts: u32, success: bool) {
+        let mut endpoints = self
+            .endpoints
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let metrics = endpoints
+            .entry(endpoint.to_owned())
+            .or_insert_with(EndpointMetrics::new);
+
+        metrics.calls += 1;
+        metrics.retries += u64::from(attempts.saturating_sub(1));
+        if success {
+            metrics.successes += 1;
+        } else {
+            metrics.errors += 1;
+        }
+        metrics.latency.observe(duration);
+    }
+}
+
+/// Metrics collected at some moment.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSnapshot {
+    /// Metrics by endpoint name (see [Endpoint](crate::request::Endpoint)).
+    pub endpoints: BTreeMap<String, EndpointMetrics>,
+}
+
+/// Metrics of single endpoint.
+#[derive(Debug, Clone)]
+pub struct EndpointMetrics {
+    /// Number of `send` calls.
+    pub calls: u64,
+    /// Number of successful calls.
+    pub successes: u64,
+    /// Number of failed calls.
+    pub errors: u64,
+    /// Number of retried attempts.
+    pub retries: u64,
+    /// Latency of calls, including retries.
+    pub latency: Histogram,
+}
+impl EndpointMetrics {
+    fn new() -> Self {
+        EndpointMetrics {
+            calls: 0,
+            successes: 0,
+            errors: 0,
+            retries: 0,
+            latency: Histogram::new(),
+        }
+    }
+}
+
+/// Latency histogram.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    /// Buckets as upper bound and number of observations not greater than it (cumulative).
+    pub buckets: Vec<(Duration, u64)>,
+    /// Number of observations.
+    pub count: u64,
+    /// Sum of observations.
+    pub sum: Duration,
+}
+impl Histogram {
+    fn new() -> Self {
+        let buckets = LATENCY_BUCKETS_MS
+            .iter()
+            .map(|ms| (Duration::from_millis(*ms), 0))
+            .collect();
+
+        Histogram {
+            buckets,
+            count: 0,
+            sum: Duration::from_secs(0),
+        }
+    }
+    fn observe(&mut self, value: Duration) {
+        for (bound, count) in &mut self.buckets {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+    /// Mean of observations.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        Some(self.sum.div_f64(self.count as f64))
+    }
+}
+
+#[test]
+fn record() {
+    let metrics = Metrics::new();
+    metrics.record("image", Duration::from_millis(70), 1, true);
+    metrics.record("image", Duration::from_millis(30), 3, false);
+
+    let snapshot = metrics.snapshot();
+    let image = &snapshot.endpoints["image"];
+    assert_eq!(
+        (image.calls, image.successes, image.errors, image.retries),
+        (2, 1, 1, 2)
+    );
+    assert_eq!(image.latency.buckets[0], (Duration::from_millis(50), 1));
+    assert_eq!(image.latency.buckets[1], (Duration::from_millis(100), 2));
+    assert_eq!(image.latency.mean(), Some(Duration::from_millis(50)));
+
+    metrics.reset();
+    assert!(metrics.snapshot().endpoints.is_empty());
+}
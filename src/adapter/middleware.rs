@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use crate::{
+    error::Error,
+    transport::{HttpRequest, HttpResponse},
+};
+
+/// Hooks run by adapters around every attempt to send a request.
+///
+/// `before_send` hooks run in order in which middleware was added to adapter and can modify
+/// the request or short-circuit it by returning a response, in which case the request is not
+/// sent and following middleware is skipped. `after_receive` hooks run in reverse order (only
+/// for middleware which `before_send` ran) and can inspect or modify the response.
+/// Error returned from any hook fails the attempt.
+/// ```
+/// use std::time::Duration;
+/// use reqwest::header::{HeaderValue, USER_AGENT};
+/// use derpiboorust::{
+///     transport::{HttpRequest, HttpResponse},
+///     Error, Middleware, SyncAdapter,
+/// };
+///
+/// struct Logger;
+/// impl Middleware for Logger {
+///     fn before_send(&self, request: &mut HttpRequest) -> Result<Option<HttpResponse>, Error> {
+///         request.headers.insert(USER_AGENT, HeaderValue::from_static("my-app/1.0"));
+///         Ok(None)
+///     }
+///     fn after_receive(
+///         &self,
+///         request: &HttpRequest,
+///         response: &mut HttpResponse,
+///         elapsed: Duration,
+///     ) -> Result<(), Error> {
+///         println!("{} {} in {:?}", request.url, response.status, elapsed);
+///         Ok(())
+///     }
+/// }
+///
+/// let adapter = SyncAdapter::new().middleware(Logger);
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Called before request is sent, returned response is used instead of sending it.
+    fn before_send(&self, _request: &mut HttpRequest) -> Result<Option<HttpResponse>, Error> {
+        Ok(None)
+    }
+    /// Called after response is received, `elapsed` is time since the middleware chain started.
+    fn after_receive(
+        &self,
+        _request: &HttpRequest,
+        _response: &mut HttpResponse,
+        _elapsed: Duration,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -3,11 +3,12 @@
 //! Both adapters are just simple wrappers over request's `Client`.
 //! They have `From<Client>` implementation, so you should not use the `new` method, better
 //! create a customized `Client` (set a timeout, User-Agent, etc.) and create an adapter from it.
+//! Any other HTTP client can be used with `with_transport` method, see [transport](crate::transport).
 //!
 //! # Example
 //! ```no_run
 //! use std::time::Duration;
-//! use reqwest::Client;
+//! use reqwest::blocking::Client;
 //! use derpiboorust::{SyncAdapter, Lists};
 //!
 //! let client = Client::builder()
@@ -19,20 +20,504 @@
 //! let request = Lists::new().page(2);
 //! let response = adapter.send(request).unwrap();
 //! ```
+//!
+//! [AsyncAdapter] returns std futures, so it can be used with `.await` on tokio runtime:
+//! ```no_run
+//! use derpiboorust::{AsyncAdapter, Search};
+//!
+//! # async fn run() -> Result<(), derpiboorust::Error> {
+//! let adapter = AsyncAdapter::new();
+//! let query = String::from("safe, luna");
+//! let response = adapter.send(Search::new(&query)).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! By default requests are sent to derpibooru.org, any other Philomena instance can be used
+//! by setting a base URL:
+//! ```no_run
+//! use reqwest::Url;
+//! use derpiboorust::{SyncAdapter, Image};
+//!
+//! let base = Url::parse("https://furbooru.org").unwrap();
+//! let adapter = SyncAdapter::new().base_url(base);
+//! let response = adapter.send(Image::new(1)).unwrap();
+//! ```
+//!
+//! Failed requests can be retried transparently, see [RetryPolicy], and request rate can be
+//! limited on the client side, see [RateLimiter]. Single sends can be limited in time
+//! and cancelled with `send_with` method, see [SendOptions]. Requests of different importance
+//! sharing one API budget can be dispatched by priority with [Scheduler].
+//!
+//! When typed response doesn't fit (e.g. API added a field not supported by models yet),
+//! response can be fetched as raw JSON with `send_raw` method, see [RawResponse], or adapter
+//! can decode responses leniently, see [models](crate::models).
+//!
+//! Requests can be rendered as curl command or HTTP text with `prepare` method,
+//! see [HttpRequest].
+//!
+//! Requests and responses can be inspected and modified by [Middleware]. Repeated requests
+//! can be made conditional with [ConditionalGet] middleware.
+//!
+//! Query parameters common for all requests (user key, filter, etc.) can be set on adapter,
+//! parameters set on request take precedence over them:
+//! ```no_run
+//! use derpiboorust::{Search, SyncAdapter};
+//!
+//! let adapter = SyncAdapter::new().key("user_account_key").filter_id(56027);
+//! // Sent with `filter_id=100073`
+//! let response = adapter.send(Search::new("safe").filter_id(100073)).unwrap();
+//! ```
 
-use failure::{err_msg, Error};
-use reqwest::StatusCode;
+use chrono::{DateTime, Utc};
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    StatusCode, Url,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use serde_path_to_error::{Path, Segment};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{field, Span};
+
+use crate::{
+    error::{Error, ErrorKind},
+    request::{default_base, Endpoint, Request},
+    transport::{HttpRequest, HttpResponse},
+};
 
 mod r#async;
+mod cancel;
+mod conditional;
+mod crawl;
+mod lenient;
+mod metrics;
+mod middleware;
+mod options;
+mod pages;
+mod rate_limit;
+mod retry;
+mod scheduler;
 mod sync;
 
-pub use self::{r#async::AsyncAdapter, sync::SyncAdapter};
+pub use self::{
+    cancel::CancellationToken,
+    conditional::ConditionalGet,
+    crawl::Crawl,
+    metrics::{EndpointMetrics, Histogram, Metrics, MetricsSnapshot},
+    middleware::Middleware,
+    options::SendOptions,
+    pages::Pages,
+    r#async::AsyncAdapter,
+    rate_limit::RateLimiter,
+    retry::RetryPolicy,
+    scheduler::{Priority, Scheduler},
+    sync::SyncAdapter,
+};
+
+/// Response decoded as raw JSON, returned by `send_raw` methods of adapters.
+#[derive(Debug, Clone)]
+pub struct RawResponse {
+    /// Response status.
+    pub status: StatusCode,
+    /// Response headers.
+    pub headers: HeaderMap,
+    /// Response body.
+    pub body: Value,
+}
+impl RawResponse {
+    fn decode(url: &Url, response: HttpResponse) -> Result<Self, Error> {
+        let body = decode(url, &response.body)?;
+
+        Ok(RawResponse {
+            status: response.status,
+            headers: response.headers,
+            body,
+        })
+    }
+}
+
+/// Settings shared by both adapters.
+struct Config {
+    base: Url,
+    retry: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    defaults: Vec<(String, String)>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    metrics: Option<Metrics>,
+    lenient: bool,
+}
+impl Config {
+    fn new() -> Self {
+        Config {
+            base: default_base(),
+            retry: RetryPolicy::none(),
+            rate_limiter: None,
+            defaults: Vec::new(),
+            middleware: Vec::new(),
+            metrics: None,
+            lenient: false,
+        }
+    }
+    fn set_default(&mut self, key: &str, value: String) {
+        self.defaults.retain(|(default_key, _)| default_key != key);
+        self.defaults.push((key.to_owned(), value));
+    }
+    /// Decoder of typed responses.
+    fn decoder<T: DeserializeOwned>(&self) -> Decoder<T> {
+        if self.lenient {
+            decode_lenient_response
+        } else {
+            decode_response
+        }
+    }
+    /// Build request URL and add default parameters missing in it.
+    fn url<'r, R: Request<'r>>(&self, request: &R) -> Result<Url, Error> {
+        let mut url = request.build_with_base(&self.base)?;
+        let missing: Vec<_> = self
+            .defaults
+            .iter()
+            .filter(|(key, _)| {
+                url.query_pairs()
+                    .all(|(request_key, _)| request_key != *key)
+            })
+            .collect();
+
+        if !missing.is_empty() {
+            url.query_pairs_mut().extend_pairs(missing);
+        }
+
+        Ok(url)
+    }
+    /// Build request as it would be sent, running `before_send` hooks of middleware
+    /// until one of them short-circuits.
+    fn prepare<'r, R: Request<'r>>(&self, request: &R) -> Result<HttpRequest, Error> {
+        let mut prepared = HttpRequest::get(self.url(request)?);
+        self.before_send(&mut prepared)?;
+
+        Ok(prepared)
+    }
+    /// Run `before_send` hooks, returns chain state for [after_receive](Config::after_receive)
+    /// and short-circuit response, if any.
+    fn before_send(
+        &self,
+        request: &mut HttpRequest,
+    ) -> Result<(Chain, Option<HttpResponse>), Error> {
+        let mut chain = Chain {
+            ran: 0,
+            started: Instant::now(),
+        };
+
+        for middleware in &self.middleware {
+            chain.ran += 1;
+            if let Some(response) = middleware.before_send(request)? {
+                return Ok((chain, Some(response)));
+            }
+        }
+
+        Ok((chain, None))
+    }
+    /// Run `after_receive` hooks of middleware which `before_send` ran, in reverse order.
+    fn after_receive(
+        &self,
+        chain: Chain,
+        request: &HttpRequest,
+        response: &mut HttpResponse,
+    ) -> Result<(), Error> {
+        for middleware in self.middleware[..chain.ran].iter().rev() {
+            middleware.after_receive(request, response, chain.started.elapsed())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Outcome of sending a request with retries, before decoding.
+struct Fetched {
+    span: Span,
+    started: Instant,
+    attempts: u32,
+    response: Result<HttpResponse, Error>,
+}
+
+impl Config {
+    /// Span of `send` call.
+    fn span(&self, url: &Url) -> Span {
+        tracing::info_span!(
+            "derpibooru_request",
+            endpoint = endpoint_name(url),
+            url = %redact(url),
+            status = field::Empty,
+            retries = field::Empty,
+            duration_ms = field::Empty,
+        )
+    }
+    /// Decode successful response and record outcome of `send` call.
+    fn complete<V>(&self, url: &Url, fetched: Fetched, decode: Decoder<V>) -> Result<V, Error> {
+        let result = fetched.response.and_then(|response| {
+            let status = response.status;
+            Ok((status, decode(url, response)?))
+        });
+        let outcome = result.as_ref().map(|(status, _)| *status);
+        self.finish(
+            &fetched.span,
+            url,
+            fetched.started,
+            fetched.attempts,
+            outcome,
+        );
+
+        result.map(|(_, value)| value)
+    }
+    /// Record outcome of `send` call to span and metrics.
+    fn finish(
+        &self,
+        span: &Span,
+        url: &Url,
+        started: Instant,
+        attempts: u32,
+        outcome: Result<StatusCode, &Error>,
+    ) {
+        let duration = started.elapsed();
+        let status = match &outcome {
+            Ok(status) => Some(*status),
+            Err(error) => error.status(),
+        };
+
+        if let Some(status) = status {
+            span.record("status", status.as_u16());
+        }
+        span.record("retries", attempts - 1);
+        span.record("duration_ms", duration.as_millis() as u64);
+
+        match &outcome {
+            Ok(_) => tracing::debug!(parent: span, "request succeeded"),
+            Err(error) => tracing::warn!(parent: span, error = %error, "request failed"),
+        }
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record(endpoint_name(url), duration, attempts, outcome.is_ok());
+        }
+    }
+}
+
+fn endpoint_name(url: &Url) -> &'static str {
+    Endpoint::of(url).map_or("unknown", Endpoint::name)
+}
+
+/// Copy of URL with user key hidden.
+pub(crate) fn redact(url: &Url) -> Url {
+    let mut redacted = url.clone();
+    if url.query_pairs().all(|(key, _)| key != "key") {
+        return redacted;
+    }
+
+    let pairs = url.query_pairs().map(|(key, value)| {
+        let value = if key == "key" {
+            "REDACTED".into()
+        } else {
+            value
+        };
+        (key, value)
+    });
+    redacted.query_pairs_mut().clear().extend_pairs(pairs);
 
-fn check_status(status: StatusCode) -> Option<Error> {
+    redacted
+}
+
+/// State of middleware chain for a single attempt.
+struct Chain {
+    ran: usize,
+    started: Instant,
+}
+
+fn check_status(response: &HttpResponse, url: &Url) -> Option<Error> {
+    let status = response.status;
+    if status == StatusCode::NOT_MODIFIED {
+        let error = Error::new(ErrorKind::NotModified)
+            .with_status(status)
+            .with_url(url.clone());
+        return Some(error);
+    }
     if status.is_client_error() || status.is_server_error() {
-        let error = format!("HTTP error: {}", status);
-        return Some(err_msg(error));
+        let mut error = Error::from_response(status, &response.text()).with_url(url.clone());
+        if let Some(delay) = retry_after(&response.headers) {
+            error = error.with_retry_after(delay);
+        }
+
+        return Some(error);
     }
 
     None
 }
+
+/// Parse `Retry-After` header, which contains either seconds or HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&Utc) - Utc::now();
+
+    Some(delay.to_std().unwrap_or_default())
+}
+
+/// Function decoding successful response.
+type Decoder<V> = fn(&Url, HttpResponse) -> Result<V, Error>;
+
+fn decode_response<T: DeserializeOwned>(url: &Url, response: HttpResponse) -> Result<T, Error> {
+    decode(url, &response.body)
+}
+
+fn decode_lenient_response<T: DeserializeOwned>(
+    url: &Url,
+    response: HttpResponse,
+) -> Result<T, Error> {
+    let value = decode(url, &response.body)?;
+    serde_path_to_error::deserialize(lenient::Lenient(value))
+        .map_err(|error| decode_error(url, &response.body, error))
+}
+
+/// Decode JSON body, on failure error reports path to the value which failed to decode.
+fn decode<T: DeserializeOwned>(url: &Url, body: &[u8]) -> Result<T, Error> {
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    serde_path_to_error::deserialize(&mut deserializer)
+        .map_err(|error| decode_error(url, body, error))
+}
+
+fn decode_error(
+    url: &Url,
+    body: &[u8],
+    error: serde_path_to_error::Error<serde_json::Error>,
+) -> Error {
+    let path = error.path().clone();
+    let mut result = Error::new(ErrorKind::Decode)
+        .with_url(url.clone())
+        .with_body(&String::from_utf8_lossy(body));
+
+    if path.iter().next().is_some() {
+        if let Some(value) = value_at(body, &path) {
+            result = result.with_excerpt(&value.to_string());
+        }
+        result = result.with_path(path.to_string());
+    }
+
+    result.with_cause(error.into_inner())
+}
+
+/// Find value at `path` in JSON body, if body is valid JSON.
+fn value_at(body: &[u8], path: &Path) -> Option<Value> {
+    let mut value: Value = serde_json::from_slice(body).ok()?;
+    for segment in path {
+        value = match (segment, value) {
+            (Segment::Seq { index }, Value::Array(mut values)) if *index < values.len() => {
+                values.swap_remove(*index)
+            }
+            (Segment::Map { key }, Value::Object(mut object)) => object.remove(key)?,
+            (Segment::Enum { .. }, value) => value,
+            _ => return None,
+        };
+    }
+
+    Some(value)
+}
+
+#[test]
+fn retry_after_header() {
+    use reqwest::header::HeaderValue;
+
+    let mut headers = HeaderMap::new();
+    assert_eq!(retry_after(&headers), None);
+
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+    assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+
+    headers.insert(
+        RETRY_AFTER,
+        HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+    );
+    assert_eq!(retry_after(&headers), Some(Duration::from_secs(0)));
+}
+
+#[test]
+fn redact_key() {
+    let url = Url::parse("https://derpibooru.org/search.json?q=safe&key=secret").unwrap();
+    let expected = Url::parse("https://derpibooru.org/search.json?q=safe&key=REDACTED").unwrap();
+
+    assert_eq!(redact(&url), expected);
+}
+
+#[test]
+fn decode_path() {
+    use crate::request::response::SearchResponse;
+
+    let url = Url::parse("https://derpibooru.org/search.json?q=safe").unwrap();
+    let body = br#"{"search": [{"id": 1, "tags": null}], "total": 1}"#;
+    let error = decode::<SearchResponse>(&url, body).unwrap_err();
+
+    assert_eq!(error.kind(), ErrorKind::Decode);
+    assert_eq!(error.url(), Some(&url));
+    assert_eq!(error.path(), Some("search[0].tags"));
+    assert_eq!(error.excerpt(), Some("null"));
+}
+
+#[test]
+fn log_without_key() {
+    use crate::{request::Search, transport::MemoryTransport, SyncAdapter};
+    use std::{
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+
+    /// Subscriber writing all fields of spans and events to a string.
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<String>>);
+    impl Visit for Capture {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            let line = format!("{}={:?}\n", field.name(), value);
+            self.0.lock().unwrap().push_str(&line);
+        }
+    }
+    impl Subscriber for Capture {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            span.record(&mut self.clone());
+            Id::from_u64(1)
+        }
+        fn record(&self, _span: &Id, values: &Record<'_>) {
+            values.record(&mut self.clone());
+        }
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut self.clone());
+        }
+        fn enter(&self, _span: &Id) {}
+        fn exit(&self, _span: &Id) {}
+    }
+
+    let capture = Capture::default();
+    let policy = RetryPolicy::new().initial_delay(Duration::from_millis(1));
+    let adapter = SyncAdapter::with_transport(MemoryTransport::new())
+        .retry(policy)
+        .key("secret");
+    let error = tracing::subscriber::with_default(capture.clone(), || {
+        adapter.send(Search::new("safe")).unwrap_err()
+    });
+
+    let logs = capture.0.lock().unwrap();
+    assert!(logs.contains("request failed"), "{}", logs);
+    assert!(!logs.contains("secret"), "{}", logs);
+    assert!(!error.to_string().contains("secret"));
+}
@@ -0,0 +1,137 @@
+use futures::{
+    future::{self, Either},
+    pin_mut,
+};
+use std::{
+    future::Future,
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::time;
+
+use super::CancellationToken;
+use crate::error::{Error, ErrorKind};
+
+/// Options of a single send, see `send_with` methods of adapters.
+///
+/// Timeout limits the whole send, including retries and waiting for rate limiter,
+/// and fails it with [Timeout](crate::ErrorKind::Timeout) error.
+/// ```no_run
+/// use std::time::Duration;
+/// use derpiboorust::{adapter::SendOptions, Image, SyncAdapter};
+///
+/// let adapter = SyncAdapter::new();
+/// let options = SendOptions::new().timeout(Duration::from_secs(2));
+/// let image = adapter.send_with(Image::new(1), options).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SendOptions {
+    timeout: Option<Duration>,
+    cancel: Option<CancellationToken>,
+}
+impl SendOptions {
+    /// Create options without timeout and cancellation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set timeout of the send.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    /// Set cancellation token.
+    pub fn cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+    /// Start limits of send started at `started`.
+    pub(crate) fn start(&self, started: Instant) -> Limits {
+        Limits {
+            deadline: self.timeout.map(|timeout| started + timeout),
+            cancel: self.cancel.clone(),
+        }
+    }
+}
+
+/// Deadline and cancellation of a send in progress.
+pub(crate) struct Limits {
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) cancel: Option<CancellationToken>,
+}
+impl Limits {
+    /// Time left until deadline, fails if send is cancelled or deadline passed.
+    pub(crate) fn remaining(&self) -> Result<Option<Duration>, Error> {
+        if let Some(token) = &self.cancel {
+            if token.is_cancelled() {
+                return Err(Error::new(ErrorKind::Cancelled));
+            }
+        }
+
+        match self.deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if remaining > Duration::from_secs(0) => Ok(Some(remaining)),
+                _ => Err(Error::deadline()),
+            },
+            None => Ok(None),
+        }
+    }
+    /// Block current thread for `delay`, fails if send is cancelled or deadline passes first.
+    pub(crate) fn sleep(&self, delay: Duration) -> Result<(), Error> {
+        let delay = match self.remaining()? {
+            Some(remaining) => delay.min(remaining),
+            None => delay,
+        };
+
+        match &self.cancel {
+            Some(token) => token.wait(delay),
+            None => thread::sleep(delay),
+        }
+
+        self.remaining().map(|_| ())
+    }
+    /// Run `future` until it completes, deadline passes or send is cancelled.
+    pub(crate) async fn guard<T, F>(&self, future: F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        self.remaining()?;
+
+        let timed = async {
+            match self.deadline {
+                Some(deadline) => time::timeout_at(time::Instant::from_std(deadline), future)
+                    .await
+                    .unwrap_or_else(|_| Err(Error::deadline())),
+                None => future.await,
+            }
+        };
+        let token = match &self.cancel {
+            Some(token) => token,
+            None => return timed.await,
+        };
+
+        let cancelled = token.cancelled();
+        pin_mut!(timed, cancelled);
+        match future::select(timed, cancelled).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => Err(Error::new(ErrorKind::Cancelled)),
+        }
+    }
+}
+
+#[test]
+fn limits() {
+    let token = CancellationToken::new();
+    let options = SendOptions::new()
+        .timeout(Duration::from_millis(20))
+        .cancel(token.clone());
+    let limits = options.start(Instant::now());
+
+    assert!(limits.remaining().unwrap().unwrap() <= Duration::from_millis(20));
+    let error = limits.sleep(Duration::from_secs(10)).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Timeout);
+
+    let limits = options.start(Instant::now());
+    token.cancel();
+    let error = limits.sleep(Duration::from_secs(10)).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Cancelled);
+}
@@ -0,0 +1,247 @@
+use futures::stream::{FuturesOrdered, StreamExt};
+use serde::Deserialize;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    marker::PhantomData,
+    task::{Context, Poll},
+};
+
+use super::SyncAdapter;
+use crate::{error::Error, request::Paginated, transport::Transport};
+
+/// Iterator over items of a paginated request, created by [SyncAdapter::pages].
+///
+/// Pages are fetched lazily, starting from the first one (page set on request is ignored),
+/// until a page is empty or all items reported by response (`total` of
+/// [SearchResponse](crate::request::response::SearchResponse)) are fetched. Failed request
+/// is yielded as error and ends iteration.
+/// ```no_run
+/// use derpiboorust::{Search, SyncAdapter};
+///
+/// let adapter = SyncAdapter::new();
+/// for image in adapter.pages(Search::new("safe, luna")).limit(100) {
+///     println!("{}", image.unwrap().id);
+/// }
+/// ```
+pub struct Pages<'s, 'r, T, R: Paginated<'r>> {
+    adapter: &'s SyncAdapter<T>,
+    request: R,
+    page: u64,
+    items: VecDeque<R::Item>,
+    fetched: u64,
+    yielded: usize,
+    limit: Option<usize>,
+    done: bool,
+    lifetime: PhantomData<&'r ()>,
+}
+impl<'s, 'r, T, R> Pages<'s, 'r, T, R>
+where
+    T: Transport,
+    R: Paginated<'r>,
+    R::ResponseValue: for<'de> Deserialize<'de>,
+{
+    pub(crate) fn new(adapter: &'s SyncAdapter<T>, request: R) -> Self {
+        Pages {
+            adapter,
+            request,
+            page: 1,
+            items: VecDeque::new(),
+            fetched: 0,
+            yielded: 0,
+            limit: None,
+            done: false,
+            lifetime: PhantomData,
+        }
+    }
+    /// Stop after `limit` items.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+    fn fetch_page(&mut self) -> Result<(), Error> {
+        let request = self.request.clone().with_page(self.page);
+        let response = self.adapter.send(request)?;
+        let total = R::total(&response);
+        let items = R::items(response);
+
+        self.page += 1;
+        self.fetched += items.len() as u64;
+        if items.is_empty() || total.is_some_and(|total| self.fetched >= total) {
+            self.done = true;
+        }
+        self.items.extend(items);
+
+        Ok(())
+    }
+}
+
+impl<'s, 'r, T, R> Iterator for Pages<'s, 'r, T, R>
+where
+    T: Transport,
+    R: Paginated<'r>,
+    R::ResponseValue: for<'de> Deserialize<'de>,
+{
+    type Item = Result<R::Item, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.limit.is_some_and(|limit| self.yielded >= limit) {
+                return None;
+            }
+            if let Some(item) = self.items.pop_front() {
+                self.yielded += 1;
+                return Some(Ok(item));
+            }
+            if self.done {
+                return None;
+            }
+
+            if let Err(error) = self.fetch_page() {
+                self.done = true;
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+/// Page fetches of [AsyncAdapter::pages](super::AsyncAdapter::pages).
+///
+/// Fetches are polled whenever the stream is, so pages ahead of the consumed one keep
+/// loading while its items are yielded.
+pub(super) struct Prefetch<S, F: Future, I> {
+    /// Starts fetch of a page, resolving to total number of items and items of the page.
+    fetch: S,
+    fetches: FuturesOrdered<F>,
+    prefetch: u64,
+    /// Next page to fetch.
+    next: u64,
+    /// Last page worth fetching, known from the total or when the end is reached.
+    last: u64,
+    /// Page items are yielded from.
+    consumed: u64,
+    received: u64,
+    fetched: u64,
+    items: VecDeque<(u64, Result<I, Error>)>,
+}
+impl<S, F, I> Prefetch<S, F, I>
+where
+    S: FnMut(u64) -> F,
+    F: Future<Output = Result<(Option<u64>, Vec<I>), Error>>,
+{
+    pub(super) fn new(prefetch: usize, fetch: S) -> Self {
+        Prefetch {
+            fetch,
+            fetches: FuturesOrdered::new(),
+            prefetch: prefetch as u64,
+            next: 1,
+            last: u64::MAX,
+            consumed: 1,
+            received: 0,
+            fetched: 0,
+            items: VecDeque::new(),
+        }
+    }
+    pub(super) fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<I, Error>>> {
+        self.poll_fetches(cx);
+        let (page, item) = match self.items.pop_front() {
+            Some(item) => item,
+            None if self.fetches.is_empty() => return Poll::Ready(None),
+            None => return Poll::Pending,
+        };
+
+        // Start fetches allowed by moving to the next page
+        if page > self.consumed {
+            self.consumed = page;
+            self.poll_fetches(cx);
+        }
+
+        Poll::Ready(Some(item))
+    }
+    /// Start fetches of pages up to `prefetch` ahead of the consumed one and collect items
+    /// of fetched pages.
+    fn poll_fetches(&mut self, cx: &mut Context<'_>) {
+        while self.next <= self.last && self.next <= self.consumed.saturating_add(self.prefetch) {
+            self.fetches.push_back((self.fetch)(self.next));
+            self.next += 1;
+        }
+
+        while let Poll::Ready(Some(result)) = self.fetches.poll_next_unpin(cx) {
+            self.received += 1;
+            let page = self.received;
+            let (total, items) = match result {
+                Ok(fetched) => fetched,
+                Err(error) => {
+                    self.items.push_back((page, Err(error)));
+                    self.finish(page);
+                    continue;
+                }
+            };
+
+            // Size of the first page tells the number of pages
+            if let (1, Some(total)) = (page, total) {
+                if !items.is_empty() {
+                    self.last = self.last.min(total.div_ceil(items.len() as u64));
+                }
+            }
+            self.fetched += items.len() as u64;
+            let done = items.is_empty() || total.is_some_and(|total| self.fetched >= total);
+            self.items
+                .extend(items.into_iter().map(|item| (page, Ok(item))));
+            if done {
+                self.finish(page);
+            }
+        }
+    }
+    /// Stop at `page`, cancelling fetches of the following pages.
+    fn finish(&mut self, page: u64) {
+        self.last = page;
+        self.fetches = FuturesOrdered::new();
+    }
+}
+
+#[test]
+fn pages() {
+    use crate::{
+        models::image::sample,
+        request::{Galleries, Request, Search},
+        transport::{HttpResponse, MemoryTransport},
+    };
+    use reqwest::StatusCode;
+    use serde_json::json;
+
+    let transport = MemoryTransport::new();
+    let page = |page, ids: &[u64]| {
+        let url = Search::new("safe").page(page).build().unwrap();
+        let images: Vec<_> = ids.iter().map(|id| sample(*id)).collect();
+        let body = json!({ "search": images, "total": 3 }).to_string();
+        transport.respond(url, HttpResponse::new(StatusCode::OK, body));
+    };
+    page(1, &[1, 2]);
+    page(2, &[3]);
+
+    let adapter = SyncAdapter::with_transport(transport.clone());
+    let ids: Vec<_> = adapter
+        .pages(Search::new("safe").page(5))
+        .map(|image| image.unwrap().id)
+        .collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+    assert_eq!(transport.requests().len(), 2);
+
+    let ids: Vec<_> = adapter
+        .pages(Search::new("safe"))
+        .limit(1)
+        .map(|image| image.unwrap().id)
+        .collect();
+    assert_eq!(ids, vec![1]);
+    assert_eq!(transport.requests().len(), 3);
+
+    let url = Galleries::new("user").page(1).build().unwrap();
+    transport.respond(url, HttpResponse::new(StatusCode::OK, "[]"));
+    let mut galleries = adapter.pages(Galleries::new("user"));
+    assert!(galleries.next().is_none());
+
+    let mut galleries = adapter.pages(Galleries::new("unknown"));
+    assert!(galleries.next().unwrap().is_err());
+    assert!(galleries.next().is_none());
+}
@@ -0,0 +1,97 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Client-side token bucket rate limiter.
+///
+/// Bucket holds up to `burst` permits and is refilled with `requests_per_second` permits
+/// per second, every request sent by adapter takes one permit (waiting for it if the bucket
+/// is empty). Limiter is cheaply cloneable and clones share the same bucket, so one limiter
+/// can be attached to several sync and async adapters.
+/// ```
+/// use derpiboorust::{AsyncAdapter, RateLimiter, SyncAdapter};
+///
+/// let limiter = RateLimiter::new(2.0, 5);
+/// let sync_adapter = SyncAdapter::new().rate_limiter(limiter.clone());
+/// let async_adapter = AsyncAdapter::new().rate_limiter(limiter);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+}
+impl RateLimiter {
+    /// Create new limiter, bucket is full initially.
+    ///
+    /// # Panics
+    /// Panics if `requests_per_second` is not positive.
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        assert!(
+            requests_per_second > 0.0,
+            "requests per second must be positive"
+        );
+
+        let burst = f64::from(burst.max(1));
+        let bucket = Bucket {
+            rate: requests_per_second,
+            burst,
+            tokens: burst,
+            updated: Instant::now(),
+        };
+
+        RateLimiter {
+            bucket: Arc::new(Mutex::new(bucket)),
+        }
+    }
+    /// Take a permit and return how long to wait before using it.
+    ///
+    /// Permits are handed out in call order: when the bucket is empty each caller is
+    /// scheduled after the previous one.
+    pub fn reserve(&self) -> Duration {
+        let mut bucket = self
+            .bucket
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+
+        bucket.tokens = (bucket.tokens + elapsed * bucket.rate).min(bucket.burst);
+        bucket.updated = now;
+        bucket.tokens -= 1.0;
+
+        if bucket.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / bucket.rate)
+        }
+    }
+    /// Block current thread until a permit is available.
+    pub fn acquire(&self) {
+        let delay = self.reserve();
+        if delay > Duration::from_secs(0) {
+            thread::sleep(delay);
+        }
+    }
+}
+
+#[test]
+fn reserve() {
+    let limiter = RateLimiter::new(10.0, 2);
+    let shared = limiter.clone();
+
+    assert_eq!(limiter.reserve(), Duration::from_secs(0));
+    assert_eq!(shared.reserve(), Duration::from_secs(0));
+
+    let third = limiter.reserve();
+    let fourth = shared.reserve();
+    assert!(third > Duration::from_millis(90) && third <= Duration::from_millis(100));
+    assert!(fourth > Duration::from_millis(190) && fourth <= Duration::from_millis(200));
+}
@@ -0,0 +1,189 @@
+use rand::Rng;
+use reqwest::StatusCode;
+use std::time::Duration;
+
+use crate::error::{Error, ErrorKind};
+
+/// Retry policy for failed requests.
+///
+/// Failed attempt is retried when response status is one of retryable statuses
+/// (`429`, `500`, `502`, `503` and `504` by default) or when a transport error
+/// (connection reset, timeout, etc.) occurs. Timeout of the whole send set by
+/// [SendOptions](super::SendOptions) isn't retried. Delay between attempts grows exponentially
+/// and is randomized by jitter, `Retry-After` header of the response is honored.
+/// ```
+/// use std::time::Duration;
+/// use derpiboorust::{RetryPolicy, SyncAdapter};
+///
+/// let policy = RetryPolicy::new()
+///     .max_attempts(5)
+///     .initial_delay(Duration::from_secs(1))
+///     .max_delay(Duration::from_secs(60));
+/// let adapter = SyncAdapter::new().retry(policy);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+    multiplier: f64,
+    jitter: f64,
+    statuses: Vec<StatusCode>,
+    transport_errors: bool,
+    retry_after: bool,
+}
+impl RetryPolicy {
+    /// Create new policy with 3 attempts and delays from 500ms up to 30s.
+    pub fn new() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+            statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            transport_errors: true,
+            retry_after: true,
+        }
+    }
+    /// Create policy which never retries (used by adapters by default).
+    pub fn none() -> Self {
+        RetryPolicy::new().max_attempts(1)
+    }
+    /// Maximum number of attempts, including the first one.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+    /// Delay before the second attempt.
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+    /// Upper limit of delay between attempts.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+    /// Factor by which delay grows after each attempt.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+    /// Fraction of delay (from 0 to 1) which is randomly subtracted from it.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+    /// Response statuses which are retried.
+    pub fn retry_statuses(mut self, statuses: Vec<StatusCode>) -> Self {
+        self.statuses = statuses;
+        self
+    }
+    /// Whether transport errors, including timeouts of attempts, are retried.
+    pub fn retry_transport_errors(mut self, retry: bool) -> Self {
+        self.transport_errors = retry;
+        self
+    }
+    /// Whether `Retry-After` response header is used as delay.
+    pub fn respect_retry_after(mut self, respect: bool) -> Self {
+        self.retry_after = respect;
+        self
+    }
+    /// Delay before the next attempt, `None` if `error` of `attempt` (starting from 1)
+    /// must not be retried.
+    pub fn delay(&self, attempt: u32, error: &Error) -> Option<Duration> {
+        if attempt >= self.max_attempts || !self.is_retryable(error) {
+            return None;
+        }
+
+        if self.retry_after {
+            if let Some(delay) = error.retry_after() {
+                return Some(delay);
+            }
+        }
+
+        // Computed in seconds, as multiplied `Duration` overflows after many attempts
+        let exponent = (attempt - 1).min(i32::MAX as u32) as i32;
+        let seconds = self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent);
+        let jitter = rand::thread_rng().gen_range(0.0..=self.jitter);
+        let seconds = seconds.min(self.max_delay.as_secs_f64()) * (1.0 - jitter);
+
+        Some(
+            Duration::try_from_secs_f64(seconds)
+                .map_or(self.max_delay, |delay| delay.min(self.max_delay)),
+        )
+    }
+    fn is_retryable(&self, error: &Error) -> bool {
+        match error.status() {
+            Some(status) => self.statuses.contains(&status),
+            None => match error.kind() {
+                ErrorKind::Transport => self.transport_errors,
+                ErrorKind::Timeout => self.transport_errors && !error.is_deadline(),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn delays() {
+    let policy = RetryPolicy::new()
+        .max_attempts(4)
+        .initial_delay(Duration::from_secs(1))
+        .max_delay(Duration::from_secs(3))
+        .jitter(0.0);
+    let error = Error::from_status(StatusCode::BAD_GATEWAY);
+
+    assert_eq!(policy.delay(1, &error), Some(Duration::from_secs(1)));
+    assert_eq!(policy.delay(2, &error), Some(Duration::from_secs(2)));
+    assert_eq!(policy.delay(3, &error), Some(Duration::from_secs(3)));
+    assert_eq!(policy.delay(4, &error), None);
+
+    let not_found = Error::from_status(StatusCode::NOT_FOUND);
+    assert_eq!(policy.delay(1, &not_found), None);
+
+    let transport = Error::new(ErrorKind::Transport);
+    assert_eq!(policy.delay(1, &transport), Some(Duration::from_secs(1)));
+
+    let timeout = Error::new(ErrorKind::Timeout);
+    assert_eq!(policy.delay(1, &timeout), Some(Duration::from_secs(1)));
+    assert_eq!(policy.delay(1, &Error::deadline()), None);
+
+    let rate_limited =
+        Error::from_status(StatusCode::TOO_MANY_REQUESTS).with_retry_after(Duration::from_secs(42));
+    assert_eq!(
+        policy.delay(1, &rate_limited),
+        Some(Duration::from_secs(42))
+    );
+}
+
+#[test]
+fn many_attempts() {
+    let policy = RetryPolicy::new()
+        .max_attempts(u32::MAX)
+        .max_delay(Duration::from_nanos(1))
+        .jitter(0.0);
+    let error = Error::new(ErrorKind::Transport);
+
+    assert_eq!(policy.delay(200, &error), Some(Duration::from_nanos(1)));
+    assert_eq!(
+        policy.delay(u32::MAX - 1, &error),
+        Some(Duration::from_nanos(1))
+    );
+
+    let policy = policy.max_delay(Duration::MAX);
+    assert_eq!(policy.delay(2000, &error), Some(Duration::MAX));
+}
@@ -0,0 +1,367 @@
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    sync::{Mutex, MutexGuard},
+};
+use tokio::{sync::oneshot, time};
+
+use super::{AsyncAdapter, Fetched, RateLimiter, SendOptions};
+use crate::{error::Error, request::Request, transport::AsyncTransport};
+
+/// Priority of a scheduled request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// Background work, e.g. crawling.
+    Low,
+    /// Default priority.
+    #[default]
+    Normal,
+    /// Interactive requests.
+    High,
+}
+
+/// Scheduler dispatching requests through a single [AsyncAdapter] by priority.
+///
+/// At most `concurrency` requests (1 by default) are in flight at once, waiting requests are
+/// dispatched in order of priority and then in order they were scheduled. Requests scheduled
+/// on behalf of a caller with [send_as](Scheduler::send_as) are additionally dispatched
+/// fairly: with the same priority, callers take turns instead of a caller with many queued
+/// requests delaying the others. Rate limiter of scheduler is applied to dispatched requests,
+/// so low priority requests can't take permits high priority ones wait for.
+/// ```no_run
+/// use derpiboorust::{
+///     adapter::{Priority, Scheduler},
+///     AsyncAdapter, Image, RateLimiter,
+/// };
+///
+/// # async fn run() {
+/// let scheduler = Scheduler::new(AsyncAdapter::new()).rate_limiter(RateLimiter::new(2.0, 1));
+///
+/// let crawl = scheduler.send_as("crawler", Image::new(1), Priority::Low);
+/// let lookup = scheduler.send(Image::new(2), Priority::High);
+/// let (crawled, looked_up) = futures::join!(crawl, lookup);
+/// # }
+/// ```
+pub struct Scheduler<T = Client> {
+    adapter: AsyncAdapter<T>,
+    limiter: Option<RateLimiter>,
+    queue: Queue,
+}
+impl<T: AsyncTransport> Scheduler<T> {
+    /// Create new scheduler sending requests one at a time through `adapter`.
+    pub fn new(adapter: AsyncAdapter<T>) -> Self {
+        Scheduler {
+            adapter,
+            limiter: None,
+            queue: Queue::new(1),
+        }
+    }
+    /// Set maximum number of requests in flight.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.queue = Queue::new(concurrency.max(1));
+        self
+    }
+    /// Set rate limiter, dispatched requests wait for its permit.
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+    /// Adapter requests are sent through.
+    pub fn adapter(&self) -> &AsyncAdapter<T> {
+        &self.adapter
+    }
+    /// Schedule a request and wait for its response.
+    pub async fn send<'r, R: Request<'r>>(
+        &self,
+        request: R,
+        priority: Priority,
+    ) -> Result<R::ResponseValue, Error>
+    where
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        let url = self.adapter.config.url(&request)?;
+        let fetched = self.fetch(&url, priority, None).await;
+        self.adapter
+            .config
+            .complete(&url, fetched, self.adapter.config.decoder())
+    }
+    /// Schedule a request on behalf of `caller` and wait for its response.
+    pub async fn send_as<'r, R: Request<'r>>(
+        &self,
+        caller: &str,
+        request: R,
+        priority: Priority,
+    ) -> Result<R::ResponseValue, Error>
+    where
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        let url = self.adapter.config.url(&request)?;
+        let fetched = self.fetch(&url, priority, Some(caller)).await;
+        self.adapter
+            .config
+            .complete(&url, fetched, self.adapter.config.decoder())
+    }
+    /// Wait for the turn of request and send it.
+    // Response isn't decoded here: awaiting a future resolving to `R::ResponseValue`
+    // makes returned future not `Send` for requests borrowing data.
+    async fn fetch(&self, url: &Url, priority: Priority, caller: Option<&str>) -> Fetched {
+        let _permit = self.dispatch(priority, caller).await;
+        self.adapter.fetch(url, &SendOptions::new()).await
+    }
+    /// Wait for the turn of request and for rate limiter.
+    async fn dispatch(&self, priority: Priority, caller: Option<&str>) -> Permit<'_> {
+        let permit = self.queue.acquire(priority, caller).await;
+        if let Some(limiter) = &self.limiter {
+            time::sleep(limiter.reserve()).await;
+        }
+
+        permit
+    }
+}
+
+/// Priority queue of requests waiting for a free slot.
+struct Queue {
+    state: Mutex<State>,
+}
+struct State {
+    available: usize,
+    waiting: BinaryHeap<Waiter>,
+    /// Number of scheduled requests, orders requests of the same priority and round.
+    scheduled: u64,
+    /// Round of the last dispatched request.
+    round: u64,
+    /// Last round and number of queued requests of each caller with queued requests.
+    callers: HashMap<String, (u64, usize)>,
+}
+impl State {
+    /// Forget caller with no more queued requests.
+    fn dequeued(&mut self, caller: &str) {
+        if let Some((_, queued)) = self.callers.get_mut(caller) {
+            *queued -= 1;
+            if *queued == 0 {
+                self.callers.remove(caller);
+            }
+        }
+    }
+}
+impl Queue {
+    fn new(slots: usize) -> Self {
+        let state = State {
+            available: slots,
+            waiting: BinaryHeap::new(),
+            scheduled: 0,
+            round: 0,
+            callers: HashMap::new(),
+        };
+
+        Queue {
+            state: Mutex::new(state),
+        }
+    }
+    async fn acquire(&self, priority: Priority, caller: Option<&str>) -> Permit<'_> {
+        let receiver = match self.enqueue(priority, caller) {
+            Some(receiver) => receiver,
+            None => return Permit { queue: self },
+        };
+
+        let mut pending = Pending {
+            queue: self,
+            receiver: Some(receiver),
+        };
+        if let Some(receiver) = pending.receiver.as_mut() {
+            // Sender is dropped only together with the queue
+            let _ = receiver.await;
+        }
+        pending.receiver = None;
+
+        Permit { queue: self }
+    }
+    /// Take a free slot or enqueue request, returns receiver notified when slot is handed
+    /// to the request.
+    fn enqueue(&self, priority: Priority, caller: Option<&str>) -> Option<oneshot::Receiver<()>> {
+        let mut state = self.lock();
+        if state.available > 0 {
+            state.available -= 1;
+            return None;
+        }
+
+        // Each caller's request goes to the round after its previous one, so callers
+        // with fewer queued requests are served in between.
+        let current = state.round;
+        let round = match caller {
+            Some(caller) => {
+                let (last, queued) = state
+                    .callers
+                    .entry(caller.to_owned())
+                    .or_insert((current, 0));
+                if *queued > 0 {
+                    *last = (*last + 1).max(current);
+                }
+                *queued += 1;
+                *last
+            }
+            None => current,
+        };
+        let (sender, receiver) = oneshot::channel();
+        let sequence = state.scheduled;
+        state.scheduled += 1;
+        state.waiting.push(Waiter {
+            priority,
+            round,
+            sequence,
+            caller: caller.map(str::to_owned),
+            sender,
+        });
+
+        Some(receiver)
+    }
+    /// Hand freed slot to the next waiting request.
+    fn release(&self) {
+        let mut state = self.lock();
+        while let Some(waiter) = state.waiting.pop() {
+            state.round = state.round.max(waiter.round);
+            if let Some(caller) = &waiter.caller {
+                state.dequeued(caller);
+            }
+            if waiter.sender.send(()).is_ok() {
+                return;
+            }
+        }
+
+        state.available += 1;
+    }
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+struct Waiter {
+    priority: Priority,
+    round: u64,
+    sequence: u64,
+    caller: Option<String>,
+    sender: oneshot::Sender<()>,
+}
+impl Waiter {
+    fn key(&self) -> (Priority, u64, u64) {
+        (
+            self.priority,
+            u64::MAX - self.round,
+            u64::MAX - self.sequence,
+        )
+    }
+}
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+impl Eq for Waiter {}
+
+/// Occupied slot, freed on drop.
+struct Permit<'a> {
+    queue: &'a Queue,
+}
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+/// Request waiting for a slot, frees the slot if it's handed to the request after
+/// the request was abandoned.
+struct Pending<'a> {
+    queue: &'a Queue,
+    receiver: Option<oneshot::Receiver<()>>,
+}
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if let Some(mut receiver) = self.receiver.take() {
+            receiver.close();
+            if receiver.try_recv().is_ok() {
+                self.queue.release();
+            }
+        }
+    }
+}
+
+#[test]
+fn fair_order() {
+    let queue = Queue::new(1);
+    assert!(queue.enqueue(Priority::Low, None).is_none());
+
+    let mut receivers = vec![
+        ("a1", queue.enqueue(Priority::Normal, Some("a")).unwrap()),
+        ("a2", queue.enqueue(Priority::Normal, Some("a")).unwrap()),
+        ("a3", queue.enqueue(Priority::Normal, Some("a")).unwrap()),
+        ("b1", queue.enqueue(Priority::Normal, Some("b")).unwrap()),
+        ("low", queue.enqueue(Priority::Low, None).unwrap()),
+        ("high", queue.enqueue(Priority::High, Some("a")).unwrap()),
+    ];
+
+    let mut order = Vec::new();
+    while !receivers.is_empty() {
+        queue.release();
+        let index = receivers
+            .iter_mut()
+            .position(|(_, receiver)| receiver.try_recv().is_ok())
+            .unwrap();
+        order.push(receivers.remove(index).0);
+    }
+
+    assert_eq!(order, vec!["high", "a1", "b1", "a2", "a3", "low"]);
+    assert!(queue.lock().callers.is_empty());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn priority() {
+    use crate::{
+        request::Image,
+        transport::{HttpResponse, MemoryTransport},
+    };
+    use reqwest::StatusCode;
+
+    let transport = MemoryTransport::new();
+    for id in 1..=2 {
+        let url = Image::new(id).build().unwrap();
+        transport.respond(url, HttpResponse::new(StatusCode::NOT_FOUND, ""));
+    }
+
+    let scheduler = Scheduler::new(AsyncAdapter::with_transport(transport.clone()));
+    let blocker = scheduler.queue.acquire(Priority::High, None).await;
+    let low = scheduler.send(Image::new(1), Priority::Low);
+    let high = scheduler.send(Image::new(2), Priority::High);
+    let release = async move { drop(blocker) };
+    let (low, high, _) = futures::join!(low, high, release);
+
+    assert_eq!(low.unwrap_err().status(), Some(StatusCode::NOT_FOUND));
+    assert_eq!(high.unwrap_err().status(), Some(StatusCode::NOT_FOUND));
+    let paths: Vec<_> = transport
+        .requests()
+        .iter()
+        .map(|request| request.url.path().to_owned())
+        .collect();
+    assert_eq!(paths, vec!["/2.json", "/1.json"]);
+}
+
+#[test]
+fn send_is_send() {
+    fn assert_send<F: Send>(_: F) {}
+    let q = String::from("safe");
+    let scheduler = Scheduler::new(AsyncAdapter::new());
+    assert_send(scheduler.send(crate::request::Search::new(&q), Priority::Normal));
+    assert_send(scheduler.send_as("a", crate::request::Search::new(&q), Priority::Normal));
+}
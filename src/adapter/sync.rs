@@ -1,40 +1,203 @@
-use failure::Error;
-use reqwest::Client;
+use reqwest::{blocking::Client, Url};
 use serde::Deserialize;
+use std::{sync::Arc, time::Instant};
 
-use super::check_status;
-use crate::request::Request;
+use super::{
+    check_status, options::Limits, Config, Crawl, Fetched, Metrics, Middleware, Pages, RateLimiter,
+    RawResponse, RetryPolicy, SendOptions,
+};
+use crate::{
+    error::Error,
+    request::{Keyset, KeysetPaginated, Paginated, Request},
+    transport::{HttpRequest, HttpResponse, Transport},
+};
 
-/// Sync adapter powered by synchronous reqwest's [Client](reqwest::Client).
-pub struct SyncAdapter {
-    client: Client,
+/// Sync adapter powered by synchronous reqwest's [Client](reqwest::blocking::Client)
+/// or any other [Transport].
+pub struct SyncAdapter<T = Client> {
+    transport: T,
+    config: Config,
 }
 impl SyncAdapter {
-    /// Creates new synchronous adapter with default [Client](reqwest::Client).
+    /// Creates new synchronous adapter with default [Client](reqwest::blocking::Client).
     pub fn new() -> Self {
-        let client = Client::new();
+        Self::from(Client::new())
+    }
+}
+impl<T: Transport> SyncAdapter<T> {
+    /// Creates new synchronous adapter sending requests through `transport`.
+    pub fn with_transport(transport: T) -> Self {
+        let config = Config::new();
 
-        SyncAdapter { client }
+        SyncAdapter { transport, config }
+    }
+    /// Set API base URL (default is [DERPIBOORU_API_BASE](crate::request::DERPIBOORU_API_BASE)).
+    pub fn base_url(mut self, base: Url) -> Self {
+        self.config.base = base;
+        self
+    }
+    /// Set retry policy for failed requests (default is [RetryPolicy::none]).
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.config.retry = policy;
+        self
+    }
+    /// Set rate limiter, every attempt to send a request waits for its permit.
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.config.rate_limiter = Some(limiter);
+        self
+    }
+    /// Add middleware, it runs after previously added ones.
+    pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.config.middleware.push(Arc::new(middleware));
+        self
+    }
+    /// Set metrics collector.
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.config.metrics = Some(metrics);
+        self
+    }
+    /// Decode responses leniently: only ids and creation dates are required, unknown fields
+    /// are kept in `extra` maps of models (see [models](crate::models)).
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.config.lenient = lenient;
+        self
+    }
+    /// Set default query parameter, added to requests which don't set it.
+    pub fn default_param<V: ToString>(mut self, key: &str, value: V) -> Self {
+        self.config.set_default(key, value.to_string());
+        self
+    }
+    /// Set default user key.
+    pub fn key(self, key: &str) -> Self {
+        self.default_param("key", key)
+    }
+    /// Set default filter.
+    pub fn filter_id(self, id: u64) -> Self {
+        self.default_param("filter_id", id)
+    }
+    /// Set default number of results on each page.
+    pub fn perpage(self, perpage: u64) -> Self {
+        self.default_param("perpage", perpage)
+    }
+    /// Build request as it would be sent by the adapter: with base URL, default parameters
+    /// and headers set by middleware. Nothing is sent.
+    pub fn prepare<'r, R: Request<'r>>(&self, request: R) -> Result<HttpRequest, Error> {
+        self.config.prepare(&request)
     }
     /// Send a request.
     pub fn send<'r, R: Request<'r>>(&self, request: R) -> Result<R::ResponseValue, Error>
     where
         R::ResponseValue: for<'de> Deserialize<'de>,
     {
-        let url = request.build()?;
-        let mut response = self.client.get(url).send()?;
+        self.send_with(request, SendOptions::new())
+    }
+    /// Send a request with timeout and cancellation set by `options`.
+    pub fn send_with<'r, R: Request<'r>>(
+        &self,
+        request: R,
+        options: SendOptions,
+    ) -> Result<R::ResponseValue, Error>
+    where
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        let url = self.config.url(&request)?;
+        let fetched = self.fetch(&url, &options);
+        self.config.complete(&url, fetched, self.config.decoder())
+    }
+    /// Send a request and return response as raw JSON, regardless of request's response type.
+    /// ```no_run
+    /// use derpiboorust::{Image, SyncAdapter};
+    ///
+    /// let adapter = SyncAdapter::new();
+    /// let response = adapter.send_raw(Image::new(1)).unwrap();
+    /// println!("{}: {}", response.status, response.body["id"]);
+    /// ```
+    pub fn send_raw<'r, R: Request<'r>>(&self, request: R) -> Result<RawResponse, Error> {
+        let url = self.config.url(&request)?;
+        let fetched = self.fetch(&url, &SendOptions::new());
+        self.config.complete(&url, fetched, RawResponse::decode)
+    }
+    /// Iterate over items of all pages of `request`, see [Pages].
+    pub fn pages<'r, R: Paginated<'r>>(&self, request: R) -> Pages<'_, 'r, T, R>
+    where
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        Pages::new(self, request)
+    }
+    /// Iterate over images of `request` walked by `keyset`, see [Crawl].
+    pub fn crawl<'r, R: KeysetPaginated<'r>>(
+        &self,
+        request: R,
+        keyset: Keyset,
+    ) -> Crawl<'_, 'r, T, R>
+    where
+        R::ResponseValue: for<'de> Deserialize<'de>,
+    {
+        Crawl::new(self, request, keyset)
+    }
+    /// Send request with retries.
+    fn fetch(&self, url: &Url, options: &SendOptions) -> Fetched {
+        let span = self.config.span(url);
+        let _entered = span.enter();
+        let started = Instant::now();
+        let limits = options.start(started);
+        let mut attempt = 1;
+
+        let response = loop {
+            if let Some(limiter) = &self.config.rate_limiter {
+                if let Err(error) = limits.sleep(limiter.reserve()) {
+                    break Err(error.with_url(url.clone()));
+                }
+            }
+
+            match self.execute(url, &limits) {
+                Ok(response) => break Ok(response),
+                Err(error) => match self.config.retry.delay(attempt, &error) {
+                    Some(delay) => {
+                        tracing::debug!(attempt, ?delay, error = %error, "retrying request");
+                        if let Err(error) = limits.sleep(delay) {
+                            break Err(error.with_url(url.clone()));
+                        }
+                    }
+                    None => break Err(error),
+                },
+            }
+
+            attempt += 1;
+        };
 
-        if let Some(error) = check_status(response.status()) {
+        drop(_entered);
+
+        Fetched {
+            span,
+            started,
+            attempts: attempt,
+            response,
+        }
+    }
+    fn execute(&self, url: &Url, limits: &Limits) -> Result<HttpResponse, Error> {
+        let mut request = HttpRequest::get(url.clone());
+        request.timeout = limits
+            .remaining()
+            .map_err(|error| error.with_url(url.clone()))?;
+        let (chain, response) = self.config.before_send(&mut request)?;
+        let mut response = match response {
+            Some(response) => response,
+            None => self.transport.execute(request.clone())?,
+        };
+        self.config.after_receive(chain, &request, &mut response)?;
+
+        if let Some(error) = check_status(&response, url) {
             return Err(error);
         }
 
-        Ok(response.json()?)
+        Ok(response)
     }
 }
 
 impl From<Client> for SyncAdapter {
     fn from(client: Client) -> Self {
-        SyncAdapter { client }
+        Self::with_transport(client)
     }
 }
 
@@ -43,3 +206,217 @@ impl Default for SyncAdapter {
         Self::new()
     }
 }
+
+#[test]
+fn retry() {
+    use crate::{
+        request::Lists,
+        transport::{HttpResponse, MemoryTransport},
+    };
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    let transport = MemoryTransport::new();
+    let url = Lists::new().build().unwrap();
+    let body = r#"{"top_scoring":[],"top_commented":[],"all_time_top_scoring":[]}"#;
+    transport.respond(
+        url.clone(),
+        HttpResponse::new(StatusCode::SERVICE_UNAVAILABLE, ""),
+    );
+    transport.respond(url, HttpResponse::new(StatusCode::OK, body));
+
+    let policy = RetryPolicy::new().initial_delay(Duration::from_millis(1));
+    let metrics = Metrics::new();
+    let adapter = SyncAdapter::with_transport(transport.clone())
+        .retry(policy)
+        .metrics(metrics.clone());
+    let response = adapter.send(Lists::new()).unwrap();
+
+    assert!(response.top_scoring.is_empty());
+    assert_eq!(transport.requests().len(), 2);
+
+    let lists = &metrics.snapshot().endpoints["lists"];
+    assert_eq!((lists.calls, lists.successes, lists.retries), (1, 1, 1));
+}
+
+#[test]
+fn default_params() {
+    use crate::{
+        request::{Image, Search},
+        transport::{HttpResponse, MemoryTransport},
+    };
+    use reqwest::StatusCode;
+
+    let transport = MemoryTransport::new();
+    let adapter = SyncAdapter::with_transport(transport.clone())
+        .key("default_key")
+        .filter_id(1)
+        .filter_id(2);
+
+    let image = Url::parse("https://derpibooru.org/1.json?key=default_key&filter_id=2").unwrap();
+    transport.respond(image, HttpResponse::new(StatusCode::NOT_FOUND, ""));
+    let error = adapter.send(Image::new(1)).unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+
+    let search =
+        Url::parse("https://derpibooru.org/search.json?q=safe&key=request_key&filter_id=2")
+            .unwrap();
+    transport.respond(search, HttpResponse::new(StatusCode::NOT_FOUND, ""));
+    let error = adapter
+        .send(Search::new("safe").key("request_key"))
+        .unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+}
+
+#[test]
+fn middleware() {
+    use crate::{
+        request::Image,
+        transport::{HttpResponse, MemoryTransport},
+    };
+    use reqwest::{
+        header::{HeaderValue, USER_AGENT},
+        StatusCode,
+    };
+    use std::time::Duration;
+
+    struct UserAgent;
+    impl Middleware for UserAgent {
+        fn before_send(&self, request: &mut HttpRequest) -> Result<Option<HttpResponse>, Error> {
+            let user_agent = HeaderValue::from_static("test");
+            request.headers.insert(USER_AGENT, user_agent);
+            Ok(None)
+        }
+        fn after_receive(
+            &self,
+            _request: &HttpRequest,
+            response: &mut HttpResponse,
+            _elapsed: Duration,
+        ) -> Result<(), Error> {
+            response.status = StatusCode::GONE;
+            Ok(())
+        }
+    }
+
+    struct ShortCircuit;
+    impl Middleware for ShortCircuit {
+        fn before_send(&self, request: &mut HttpRequest) -> Result<Option<HttpResponse>, Error> {
+            if request.url.path() == "/2.json" {
+                return Ok(Some(HttpResponse::new(StatusCode::NOT_FOUND, "")));
+            }
+
+            Ok(None)
+        }
+    }
+
+    let transport = MemoryTransport::new();
+    transport.respond(
+        Image::new(1).build().unwrap(),
+        HttpResponse::new(StatusCode::OK, "{}"),
+    );
+
+    let adapter = SyncAdapter::with_transport(transport.clone())
+        .middleware(UserAgent)
+        .middleware(ShortCircuit);
+
+    let error = adapter.send(Image::new(1)).unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::GONE));
+    assert_eq!(transport.requests()[0].headers[USER_AGENT], "test");
+
+    let error = adapter.send(Image::new(2)).unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::GONE));
+    assert_eq!(transport.requests().len(), 1);
+}
+
+#[test]
+fn timeout_and_cancel() {
+    use crate::{
+        adapter::CancellationToken, request::Image, transport::MemoryTransport, ErrorKind,
+    };
+    use std::time::Duration;
+
+    let limiter = RateLimiter::new(0.1, 1);
+    let adapter = SyncAdapter::with_transport(MemoryTransport::new()).rate_limiter(limiter);
+    adapter.send(Image::new(1)).unwrap_err();
+
+    let started = Instant::now();
+    let options = SendOptions::new().timeout(Duration::from_millis(10));
+    let error = adapter.send_with(Image::new(1), options).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Timeout);
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let token = CancellationToken::new();
+    token.cancel();
+    let options = SendOptions::new().cancel(token);
+    let error = adapter.send_with(Image::new(1), options).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Cancelled);
+}
+
+#[test]
+fn lenient() {
+    use crate::{
+        request::{Image, Search},
+        transport::{HttpResponse, MemoryTransport},
+        ErrorKind,
+    };
+    use reqwest::StatusCode;
+
+    let transport = MemoryTransport::new();
+    let body = r#"{
+        "id": 1,
+        "created_at": "2020-01-01T00:00:00Z",
+        "updated_at": "2020-01-01T00:00:00Z",
+        "first_seen_at": "2020-01-01T00:00:00Z",
+        "animated": true
+    }"#;
+    transport.respond(
+        Image::new(1).build().unwrap(),
+        HttpResponse::new(StatusCode::OK, body),
+    );
+    let body = r#"{"search": [{"id": 2, "score": 5}], "total": 1}"#;
+    transport.respond(
+        Search::new("safe").build().unwrap(),
+        HttpResponse::new(StatusCode::OK, body),
+    );
+
+    let strict = SyncAdapter::with_transport(transport.clone());
+    assert!(strict.send(Image::new(1)).is_err());
+
+    let adapter = SyncAdapter::with_transport(transport).lenient(true);
+    let image = adapter.send(Image::new(1)).unwrap();
+    assert_eq!((image.id, image.score), (1, 0));
+    assert_eq!(image.extra["animated"], true);
+
+    let error = adapter.send(Search::new("safe")).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Decode);
+    assert_eq!(error.path(), Some("search[0]"));
+}
+
+#[test]
+fn retry_timeout() {
+    use crate::{request::Lists, ErrorKind};
+    use reqwest::StatusCode;
+    use std::{
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+
+    /// Transport timing out on the first attempt.
+    #[derive(Default)]
+    struct Flaky(AtomicU32);
+    impl Transport for Flaky {
+        fn execute(&self, _request: HttpRequest) -> Result<HttpResponse, Error> {
+            if self.0.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(Error::new(ErrorKind::Timeout));
+            }
+
+            let body = r#"{"top_scoring":[],"top_commented":[],"all_time_top_scoring":[]}"#;
+            Ok(HttpResponse::new(StatusCode::OK, body))
+        }
+    }
+
+    let policy = RetryPolicy::new().initial_delay(Duration::from_millis(1));
+    let adapter = SyncAdapter::with_transport(Flaky::default()).retry(policy);
+    adapter.send(Lists::new()).unwrap();
+    assert_eq!(adapter.transport.0.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use super::{CacheEntry, CacheStore};
+use crate::{
+    adapter::redact,
+    error::Error,
+    transport::{io_error, StoredResponse},
+};
+
+/// On-disk cache store.
+///
+/// Every entry is kept in its own JSON file inside the store directory, so the store can be
+/// shared between processes. Unreadable entries are treated as missing. User key is kept only
+/// in hashed file names, entries store URLs with the key redacted.
+#[derive(Debug, Clone)]
+pub struct DiskStore {
+    dir: PathBuf,
+}
+impl DiskStore {
+    /// Create new store in directory, creating the directory if needed.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(io_error)?;
+
+        Ok(DiskStore { dir })
+    }
+    /// Store directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    fn path(&self, url: &Url) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", fnv1a(url.as_str())))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredEntry {
+    /// Redacted URL, tells apart URLs with colliding hashes.
+    url: String,
+    stored_at: DateTime<Utc>,
+    #[serde(flatten)]
+    response: StoredResponse,
+}
+
+impl CacheStore for DiskStore {
+    fn get(&self, url: &Url) -> Result<Option<CacheEntry>, Error> {
+        let file = match File::open(self.path(url)) {
+            Ok(file) => file,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(io_error(error)),
+        };
+
+        let stored: StoredEntry = match serde_json::from_reader(BufReader::new(file)) {
+            Ok(stored) => stored,
+            Err(_) => return Ok(None),
+        };
+        if stored.url != redact(url).as_str() {
+            return Ok(None);
+        }
+
+        let stored_at = stored.stored_at;
+        let entry = stored
+            .response
+            .into_response()
+            .ok()
+            .map(|response| CacheEntry {
+                response,
+                stored_at,
+            });
+
+        Ok(entry)
+    }
+    fn put(&self, url: &Url, entry: CacheEntry) -> Result<(), Error> {
+        let stored = StoredEntry {
+            url: redact(url).to_string(),
+            stored_at: entry.stored_at,
+            response: StoredResponse::from(&entry.response),
+        };
+
+        // Write to temporary file first, so other processes never see partial entry
+        let path = self.path(url);
+        let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        let file = File::create(&tmp_path).map_err(io_error)?;
+        serde_json::to_writer(BufWriter::new(file), &stored).map_err(io_error)?;
+
+        fs::rename(tmp_path, path).map_err(io_error)
+    }
+    fn remove(&self, url: &Url) -> Result<(), Error> {
+        match fs::remove_file(self.path(url)) {
+            Ok(()) => Ok(()),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(io_error(error)),
+        }
+    }
+}
+
+/// 64-bit FNV-1a hash, stable between builds (unlike `DefaultHasher`).
+fn fnv1a(value: &str) -> u64 {
+    value.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[test]
+fn disk_store() {
+    use crate::transport::HttpResponse;
+    use reqwest::StatusCode;
+
+    let dir = std::env::temp_dir().join(format!("derpiboorust-cache-{}", std::process::id()));
+    let store = DiskStore::new(&dir).unwrap();
+    let url = Url::parse("https://derpibooru.org/1.json").unwrap();
+
+    assert!(store.get(&url).unwrap().is_none());
+
+    let entry = CacheEntry::new(HttpResponse::new(StatusCode::OK, r#"{"id":1}"#));
+    store.put(&url, entry.clone()).unwrap();
+    let stored = store.get(&url).unwrap().unwrap();
+    assert_eq!(stored.response.body, entry.response.body);
+    assert_eq!(stored.stored_at, entry.stored_at);
+
+    store.remove(&url).unwrap();
+    assert!(store.get(&url).unwrap().is_none());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn key_not_stored() {
+    use crate::transport::HttpResponse;
+    use reqwest::StatusCode;
+
+    let dir = std::env::temp_dir().join(format!("derpiboorust-key-{}", std::process::id()));
+    let store = DiskStore::new(&dir).unwrap();
+    let url = Url::parse("https://derpibooru.org/1.json?key=secret").unwrap();
+    let other = Url::parse("https://derpibooru.org/1.json?key=other").unwrap();
+
+    let entry = CacheEntry::new(HttpResponse::new(StatusCode::OK, r#"{"id":1}"#));
+    store.put(&url, entry).unwrap();
+    let contents = fs::read_to_string(store.path(&url)).unwrap();
+    assert!(!contents.contains("secret"));
+    assert!(contents.contains("key=REDACTED"));
+
+    assert!(store.get(&url).unwrap().is_some());
+    assert!(store.get(&other).unwrap().is_none());
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,45 @@
+use reqwest::Url;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use super::{CacheEntry, CacheStore};
+use crate::error::Error;
+
+/// In-memory cache store.
+///
+/// Store is cheaply cloneable and clones share entries.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    entries: Arc<Mutex<HashMap<Url, CacheEntry>>>,
+}
+impl MemoryStore {
+    /// Create new empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Remove all entries.
+    pub fn clear(&self) {
+        self.entries().clear();
+    }
+    fn entries(&self) -> MutexGuard<'_, HashMap<Url, CacheEntry>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, url: &Url) -> Result<Option<CacheEntry>, Error> {
+        Ok(self.entries().get(url).cloned())
+    }
+    fn put(&self, url: &Url, entry: CacheEntry) -> Result<(), Error> {
+        self.entries().insert(url.clone(), entry);
+        Ok(())
+    }
+    fn remove(&self, url: &Url) -> Result<(), Error> {
+        self.entries().remove(url);
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+//! Response caching.
+//!
+//! [CachingTransport] wraps any transport and caches successful responses keyed by request
+//! URL (the one built by [Request](crate::Request)), so repeated requests don't burn quota.
+//! Entries live for configurable time, which can differ per [Endpoint], and are kept in a
+//! [CacheStore]: in memory ([MemoryStore]) or on disk ([DiskStore], shared between processes).
+//!
+//! Expired entries which have validators (`ETag` or `Last-Modified` headers) are revalidated
+//! with conditional request, `304 Not Modified` response renews the entry and its response
+//! is served.
+//!
+//! In offline mode the inner transport is never used: cached entries are served regardless
+//! of their age and a request without cached response fails with
+//! [NotCached](crate::ErrorKind::NotCached) error.
+//!
+//! # Example
+//! ```no_run
+//! use std::time::Duration;
+//! use reqwest::blocking::Client;
+//! use derpiboorust::{
+//!     cache::{CachingTransport, DiskStore},
+//!     request::Endpoint,
+//!     Image, SyncAdapter,
+//! };
+//!
+//! let store = DiskStore::new("/tmp/derpibooru-cache").unwrap();
+//! let transport = CachingTransport::new(Client::new(), store)
+//!     .ttl(Duration::from_secs(60))
+//!     .endpoint_ttl(Endpoint::Image, Duration::from_secs(24 * 60 * 60));
+//!
+//! let adapter = SyncAdapter::with_transport(transport);
+//! let image = adapter.send(Image::new(1)).unwrap();
+//! ```
+use chrono::{DateTime, Utc};
+use reqwest::{Method, StatusCode, Url};
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    error::{Error, ErrorKind},
+    request::Endpoint,
+    transport::{
+        set_validators, AsyncTransport, HttpRequest, HttpResponse, ResponseFuture, Transport,
+    },
+};
+
+mod disk;
+mod memory;
+
+pub use self::{disk::DiskStore, memory::MemoryStore};
+
+/// Cached response.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub response: HttpResponse,
+    pub stored_at: DateTime<Utc>,
+}
+impl CacheEntry {
+    /// Create new entry stored now.
+    pub fn new(response: HttpResponse) -> Self {
+        CacheEntry {
+            response,
+            stored_at: Utc::now(),
+        }
+    }
+    /// Age of the entry.
+    pub fn age(&self) -> Duration {
+        (Utc::now() - self.stored_at).to_std().unwrap_or_default()
+    }
+}
+
+/// Storage of cached responses.
+pub trait CacheStore {
+    /// Get entry for URL.
+    fn get(&self, url: &Url) -> Result<Option<CacheEntry>, Error>;
+    /// Store entry for URL, replacing existing one.
+    fn put(&self, url: &Url, entry: CacheEntry) -> Result<(), Error>;
+    /// Remove entry for URL.
+    fn remove(&self, url: &Url) -> Result<(), Error>;
+}
+
+/// Transport which caches successful responses of the inner transport.
+pub struct CachingTransport<T, S> {
+    inner: T,
+    cache: Cache<S>,
+}
+struct Cache<S> {
+    store: S,
+    ttl: Duration,
+    endpoint_ttls: HashMap<Endpoint, Duration>,
+    offline: bool,
+}
+impl<T, S: CacheStore> CachingTransport<T, S> {
+    /// Create new caching transport with 5 minutes entry lifetime.
+    pub fn new(inner: T, store: S) -> Self {
+        let cache = Cache {
+            store,
+            ttl: Duration::from_secs(5 * 60),
+            endpoint_ttls: HashMap::new(),
+            offline: false,
+        };
+
+        CachingTransport { inner, cache }
+    }
+    /// Lifetime of entries for endpoints without own lifetime.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.cache.ttl = ttl;
+        self
+    }
+    /// Lifetime of entries for endpoint.
+    pub fn endpoint_ttl(mut self, endpoint: Endpoint, ttl: Duration) -> Self {
+        self.cache.endpoint_ttls.insert(endpoint, ttl);
+        self
+    }
+    /// When set, serve only cached entries (regardless of their age).
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.cache.offline = offline;
+        self
+    }
+    /// Underlying store.
+    pub fn store(&self) -> &S {
+        &self.cache.store
+    }
+}
+/// Result of cache lookup.
+enum Lookup {
+    /// Fresh cached response.
+    Hit(HttpResponse),
+    /// Request must be sent, with expired entry if request revalidates it.
+    Miss(Option<CacheEntry>),
+}
+
+impl<S: CacheStore> Cache<S> {
+    /// Look up cached response, request is made conditional when revalidating expired entry.
+    fn lookup(&self, request: &mut HttpRequest) -> Result<Lookup, Error> {
+        if request.method != Method::GET {
+            return Ok(Lookup::Miss(None));
+        }
+
+        let entry = self.store.get(&request.url)?;
+        match entry {
+            Some(entry) if self.offline || entry.age() < self.ttl(&request.url) => {
+                Ok(Lookup::Hit(entry.response))
+            }
+            Some(entry) if set_validators(request, &entry.response) => {
+                Ok(Lookup::Miss(Some(entry)))
+            }
+            _ if self.offline => {
+                Err(Error::new(ErrorKind::NotCached).with_url(request.url.clone()))
+            }
+            _ => Ok(Lookup::Miss(None)),
+        }
+    }
+    /// Store received response, or renew revalidated entry.
+    fn complete(
+        &self,
+        url: &Url,
+        revalidated: Option<CacheEntry>,
+        response: HttpResponse,
+    ) -> Result<HttpResponse, Error> {
+        if let (StatusCode::NOT_MODIFIED, Some(entry)) = (response.status, revalidated) {
+            let entry = CacheEntry::new(entry.response);
+            self.store.put(url, entry.clone())?;
+
+            return Ok(entry.response);
+        }
+
+        if response.status.is_success() {
+            self.store.put(url, CacheEntry::new(response.clone()))?;
+        }
+
+        Ok(response)
+    }
+    fn ttl(&self, url: &Url) -> Duration {
+        Endpoint::of(url)
+            .and_then(|endpoint| self.endpoint_ttls.get(&endpoint))
+            .cloned()
+            .unwrap_or(self.ttl)
+    }
+}
+
+impl<T: Transport, S: CacheStore> Transport for CachingTransport<T, S> {
+    fn execute(&self, mut request: HttpRequest) -> Result<HttpResponse, Error> {
+        let revalidated = match self.cache.lookup(&mut request)? {
+            Lookup::Hit(response) => return Ok(response),
+            Lookup::Miss(revalidated) => revalidated,
+        };
+
+        let url = request.url.clone();
+        let response = self.inner.execute(request)?;
+
+        self.cache.complete(&url, revalidated, response)
+    }
+}
+
+impl<T, S> AsyncTransport for CachingTransport<T, S>
+where
+    T: AsyncTransport + Sync,
+    S: CacheStore + Sync,
+{
+    fn execute(&self, mut request: HttpRequest) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let revalidated = match self.cache.lookup(&mut request)? {
+                Lookup::Hit(response) => return Ok(response),
+                Lookup::Miss(revalidated) => revalidated,
+            };
+
+            let url = request.url.clone();
+            let response = self.inner.execute(request).await?;
+
+            self.cache.complete(&url, revalidated, response)
+        })
+    }
+}
+
+#[test]
+fn caching() {
+    use crate::transport::MemoryTransport;
+    use reqwest::StatusCode;
+
+    let url = Url::parse("https://derpibooru.org/1.json").unwrap();
+    let memory = MemoryTransport::new();
+    memory.respond(url.clone(), HttpResponse::new(StatusCode::OK, "{}"));
+
+    let transport = CachingTransport::new(memory.clone(), MemoryStore::new())
+        .endpoint_ttl(Endpoint::Lists, Duration::from_secs(0));
+    Transport::execute(&transport, HttpRequest::get(url.clone())).unwrap();
+    Transport::execute(&transport, HttpRequest::get(url.clone())).unwrap();
+    assert_eq!(memory.requests().len(), 1);
+
+    let lists = Url::parse("https://derpibooru.org/lists.json").unwrap();
+    memory.respond(lists.clone(), HttpResponse::new(StatusCode::OK, "{}"));
+    Transport::execute(&transport, HttpRequest::get(lists.clone())).unwrap();
+    Transport::execute(&transport, HttpRequest::get(lists)).unwrap();
+    assert_eq!(memory.requests().len(), 3);
+
+    let store = transport.store().clone();
+    let offline = CachingTransport::new(memory.clone(), store).offline(true);
+    Transport::execute(&offline, HttpRequest::get(url)).unwrap();
+    let missing = Url::parse("https://derpibooru.org/2.json").unwrap();
+    let error = Transport::execute(&offline, HttpRequest::get(missing)).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotCached);
+    assert_eq!(memory.requests().len(), 3);
+}
+
+#[test]
+fn revalidation() {
+    use crate::transport::MemoryTransport;
+    use reqwest::header::{HeaderValue, ETAG, IF_NONE_MATCH};
+
+    let url = Url::parse("https://derpibooru.org/lists.json").unwrap();
+    let mut response = HttpResponse::new(StatusCode::OK, "{}");
+    response
+        .headers
+        .insert(ETAG, HeaderValue::from_static("\"v1\""));
+
+    let memory = MemoryTransport::new();
+    memory.respond(url.clone(), response);
+    memory.respond(url.clone(), HttpResponse::new(StatusCode::NOT_MODIFIED, ""));
+
+    let transport =
+        CachingTransport::new(memory.clone(), MemoryStore::new()).ttl(Duration::from_secs(0));
+    Transport::execute(&transport, HttpRequest::get(url.clone())).unwrap();
+    let revalidated = Transport::execute(&transport, HttpRequest::get(url)).unwrap();
+
+    assert_eq!(revalidated.status, StatusCode::OK);
+    assert_eq!(revalidated.body, b"{}");
+    assert_eq!(memory.requests()[1].headers[IF_NONE_MATCH], "\"v1\"");
+}
@@ -0,0 +1,416 @@
+//! Error types.
+//!
+//! Every fallible operation of this crate returns [Error], which carries the [ErrorKind]
+//! together with all known context: request URL, HTTP status, response body snippet
+//! and the underlying cause. When the API explains a rejection in the response body,
+//! the explanation is available as [ApiError]. When a response doesn't match the models,
+//! error points to the JSON value which failed to decode (see [Error::path]).
+//!
+//! # Example
+//! ```no_run
+//! use derpiboorust::{ErrorKind, Image, SyncAdapter};
+//!
+//! let adapter = SyncAdapter::new();
+//! match adapter.send(Image::new(1)) {
+//!     Ok(image) => println!("{:?}", image),
+//!     Err(ref error) if error.kind() == ErrorKind::NotFound => println!("no such image"),
+//!     Err(error) => println!("{}", error),
+//! }
+//! ```
+use failure::{Backtrace, Fail};
+use reqwest::{StatusCode, Url};
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt, time::Duration};
+use url::ParseError;
+
+use crate::adapter::redact;
+
+/// Maximum length (in bytes) of response body stored in error.
+const BODY_SNIPPET_LEN: usize = 1024;
+/// Maximum length (in bytes) of JSON value excerpt stored in decode error.
+const EXCERPT_LEN: usize = 256;
+
+/// Error kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Request URL can't be built.
+    InvalidRequest,
+    /// Connection, TLS or other transport failure.
+    Transport,
+    /// Server responded with `404 Not Found`.
+    NotFound,
+    /// Server responded with `401 Unauthorized` or `403 Forbidden`.
+    Forbidden,
+    /// Server responded with `429 Too Many Requests`.
+    RateLimited,
+    /// Server responded with other 4xx status.
+    Client,
+    /// Server responded with 5xx status.
+    Server,
+    /// Response body can't be decoded.
+    Decode,
+    /// File system error.
+    Io,
+    /// Response is not cached and cache is in offline mode.
+    NotCached,
+    /// Server responded with `304 Not Modified` to conditional request.
+    NotModified,
+    /// Request didn't complete in time.
+    Timeout,
+    /// Request was cancelled.
+    Cancelled,
+}
+impl ErrorKind {
+    /// Classify HTTP error status.
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::NOT_FOUND => ErrorKind::NotFound,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorKind::Forbidden,
+            StatusCode::TOO_MANY_REQUESTS => ErrorKind::RateLimited,
+            status if status.is_server_error() => ErrorKind::Server,
+            _ => ErrorKind::Client,
+        }
+    }
+}
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            ErrorKind::InvalidRequest => "invalid request",
+            ErrorKind::Transport => "transport error",
+            ErrorKind::NotFound => "not found",
+            ErrorKind::Forbidden => "forbidden",
+            ErrorKind::RateLimited => "rate limited",
+            ErrorKind::Client => "client error",
+            ErrorKind::Server => "server error",
+            ErrorKind::Decode => "decode error",
+            ErrorKind::Io => "I/O error",
+            ErrorKind::NotCached => "response is not cached",
+            ErrorKind::NotModified => "not modified",
+            ErrorKind::Timeout => "timed out",
+            ErrorKind::Cancelled => "cancelled",
+        };
+
+        f.write_str(description)
+    }
+}
+
+/// Error explanation sent by the API.
+///
+/// Philomena reports errors either as `{"error": "message"}` or as
+/// `{"errors": {"field": ["message", ...]}}`, both forms are supported.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ApiError {
+    /// Error message.
+    pub message: Option<String>,
+    /// Field-level errors.
+    pub details: BTreeMap<String, Vec<String>>,
+}
+impl ApiError {
+    /// Parse error response body, `None` if body contains no error explanation.
+    pub fn parse(body: &str) -> Option<Self> {
+        let object = match serde_json::from_str(body) {
+            Ok(Value::Object(object)) => object,
+            _ => return None,
+        };
+        let mut api_error = ApiError::default();
+
+        for key in &["error", "message"] {
+            if let Some(Value::String(message)) = object.get(*key) {
+                api_error.message = Some(message.clone());
+                break;
+            }
+        }
+
+        match object.get("errors") {
+            Some(Value::Object(fields)) => {
+                for (field, messages) in fields {
+                    api_error
+                        .details
+                        .insert(field.clone(), messages_of(messages));
+                }
+            }
+            Some(messages) if api_error.message.is_none() => {
+                let messages = messages_of(messages);
+                if !messages.is_empty() {
+                    api_error.message = Some(messages.join("; "));
+                }
+            }
+            _ => {}
+        }
+
+        if api_error.message.is_none() && api_error.details.is_empty() {
+            return None;
+        }
+
+        Some(api_error)
+    }
+}
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(message) = &self.message {
+            parts.push(message.clone());
+        }
+        for (field, messages) in &self.details {
+            parts.push(format!("{} {}", field, messages.join(", ")));
+        }
+
+        f.write_str(&parts.join("; "))
+    }
+}
+
+fn messages_of(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(message) => vec![message.clone()],
+        Value::Array(values) => values.iter().flat_map(messages_of).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Crate error.
+#[derive(Debug)]
+pub struct Error {
+    inner: Box<Inner>,
+}
+#[derive(Debug)]
+struct Inner {
+    kind: ErrorKind,
+    url: Option<Url>,
+    status: Option<StatusCode>,
+    body: Option<String>,
+    api: Option<ApiError>,
+    retry_after: Option<Duration>,
+    path: Option<String>,
+    excerpt: Option<String>,
+    cause: Option<failure::Error>,
+    /// Deadline of the whole send passed, so it must not be retried.
+    deadline: bool,
+}
+impl Error {
+    pub(crate) fn new(kind: ErrorKind) -> Self {
+        let inner = Inner {
+            kind,
+            url: None,
+            status: None,
+            body: None,
+            api: None,
+            retry_after: None,
+            path: None,
+            excerpt: None,
+            cause: None,
+            deadline: false,
+        };
+
+        Error {
+            inner: Box::new(inner),
+        }
+    }
+    /// Timeout error of the whole send, unlike timeout of a single attempt it's not retried.
+    pub(crate) fn deadline() -> Self {
+        let mut error = Error::new(ErrorKind::Timeout);
+        error.inner.deadline = true;
+
+        error
+    }
+    /// Error for HTTP error status.
+    pub(crate) fn from_status(status: StatusCode) -> Self {
+        Error::new(ErrorKind::from_status(status)).with_status(status)
+    }
+    /// Error for HTTP error status with explanation parsed from response body.
+    pub(crate) fn from_response(status: StatusCode, body: &str) -> Self {
+        let mut error = Error::from_status(status).with_body(body);
+        error.inner.api = ApiError::parse(body);
+
+        error
+    }
+    pub(crate) fn with_url(mut self, url: Url) -> Self {
+        self.inner.url = Some(redact(&url));
+        self
+    }
+    pub(crate) fn with_status(mut self, status: StatusCode) -> Self {
+        self.inner.status = Some(status);
+        self
+    }
+    pub(crate) fn with_body(mut self, body: &str) -> Self {
+        self.inner.body = Some(snippet(body, BODY_SNIPPET_LEN));
+        self
+    }
+    pub(crate) fn with_path(mut self, path: String) -> Self {
+        self.inner.path = Some(path);
+        self
+    }
+    pub(crate) fn with_excerpt(mut self, excerpt: &str) -> Self {
+        self.inner.excerpt = Some(snippet(excerpt, EXCERPT_LEN));
+        self
+    }
+    pub(crate) fn with_retry_after(mut self, delay: Duration) -> Self {
+        self.inner.retry_after = Some(delay);
+        self
+    }
+    pub(crate) fn with_cause<E: Into<failure::Error>>(mut self, cause: E) -> Self {
+        self.inner.cause = Some(cause.into());
+        self
+    }
+    pub(crate) fn is_deadline(&self) -> bool {
+        self.inner.deadline
+    }
+    /// Error kind.
+    pub fn kind(&self) -> ErrorKind {
+        self.inner.kind
+    }
+    /// Request URL, with user key redacted.
+    pub fn url(&self) -> Option<&Url> {
+        self.inner.url.as_ref()
+    }
+    /// HTTP status of response.
+    pub fn status(&self) -> Option<StatusCode> {
+        self.inner.status
+    }
+    /// Beginning of the response body.
+    pub fn body(&self) -> Option<&str> {
+        self.inner.body.as_deref()
+    }
+    /// Error explanation sent by the API.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        self.inner.api.as_ref()
+    }
+    /// Delay requested by `Retry-After` response header.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.inner.retry_after
+    }
+    /// Path to JSON value which failed to decode, like `search[17].representations.tall`.
+    pub fn path(&self) -> Option<&str> {
+        self.inner.path.as_deref()
+    }
+    /// Beginning of JSON value which failed to decode.
+    pub fn excerpt(&self) -> Option<&str> {
+        self.inner.excerpt.as_deref()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.inner.kind)?;
+        if let Some(status) = self.inner.status {
+            write!(f, " ({})", status)?;
+        }
+        if let Some(url) = &self.inner.url {
+            write!(f, " for {}", url)?;
+        }
+        if let Some(path) = &self.inner.path {
+            write!(f, " at {}", path)?;
+        }
+        if let Some(api) = &self.inner.api {
+            write!(f, ": {}", api)?;
+        }
+        if let Some(cause) = &self.inner.cause {
+            write!(f, ": {}", cause)?;
+        }
+        if let Some(excerpt) = &self.inner.excerpt {
+            write!(f, " (got {})", excerpt)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.inner.cause.as_ref().map(failure::Error::as_fail)
+    }
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.inner.cause.as_ref().map(failure::Error::backtrace)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::new(ErrorKind::InvalidRequest).with_cause(error)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        let kind = match error.status() {
+            Some(status) => ErrorKind::from_status(status),
+            None if error.is_timeout() => ErrorKind::Timeout,
+            None if error.is_decode() => ErrorKind::Decode,
+            None => ErrorKind::Transport,
+        };
+        let mut result = Error::new(kind);
+        result.inner.url = error.url().map(redact);
+        result.inner.status = error.status();
+
+        // Reqwest's message contains the URL with user key
+        result.with_cause(error.without_url())
+    }
+}
+
+fn snippet(body: &str, len: usize) -> String {
+    if body.len() <= len {
+        return body.to_owned();
+    }
+
+    let mut end = len;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!("{}...", &body[..end])
+}
+
+#[test]
+fn status_kind() {
+    assert_eq!(
+        ErrorKind::from_status(StatusCode::NOT_FOUND),
+        ErrorKind::NotFound
+    );
+    assert_eq!(
+        ErrorKind::from_status(StatusCode::FORBIDDEN),
+        ErrorKind::Forbidden
+    );
+    assert_eq!(
+        ErrorKind::from_status(StatusCode::TOO_MANY_REQUESTS),
+        ErrorKind::RateLimited
+    );
+    assert_eq!(
+        ErrorKind::from_status(StatusCode::BAD_REQUEST),
+        ErrorKind::Client
+    );
+    assert_eq!(
+        ErrorKind::from_status(StatusCode::BAD_GATEWAY),
+        ErrorKind::Server
+    );
+}
+
+#[test]
+fn body_snippet() {
+    let body = "ä".repeat(BODY_SNIPPET_LEN);
+    let error = Error::from_status(StatusCode::BAD_GATEWAY).with_body(&body);
+    let stored = error.body().unwrap();
+
+    assert!(stored.len() <= BODY_SNIPPET_LEN + 3);
+    assert!(stored.ends_with("..."));
+}
+
+#[test]
+fn api_error() {
+    let error = Error::from_response(
+        StatusCode::BAD_REQUEST,
+        r#"{"error": "Search query failed to parse"}"#,
+    );
+    let api = error.api_error().unwrap();
+
+    assert_eq!(api.message.as_deref(), Some("Search query failed to parse"));
+    assert!(error
+        .to_string()
+        .ends_with(": Search query failed to parse"));
+
+    let api =
+        ApiError::parse(r#"{"errors": {"q": ["is invalid"], "perpage": "too big"}}"#).unwrap();
+    assert_eq!(api.message, None);
+    assert_eq!(api.details["q"], vec!["is invalid"]);
+    assert_eq!(api.details["perpage"], vec!["too big"]);
+
+    assert_eq!(ApiError::parse("<html>Bad Gateway</html>"), None);
+}
@@ -13,8 +13,19 @@
 //! ```
 
 pub mod adapter;
+pub mod cache;
+pub mod error;
 pub mod models;
+pub mod query;
 pub mod request;
+pub mod transport;
 
-pub use adapter::{AsyncAdapter, SyncAdapter};
-pub use request::{Bound, Galleries, Gallery, Image, Images, Lists, Order, Search, Watched};
+pub use adapter::{
+    AsyncAdapter, ConditionalGet, Metrics, Middleware, RateLimiter, RawResponse, RetryPolicy,
+    SyncAdapter,
+};
+pub use error::{ApiError, Error, ErrorKind};
+pub use request::{
+    Bound, Galleries, Gallery, Image, Images, Keyset, KeysetPaginated, Lists, Order, Paginated,
+    Request, Search, Watched,
+};
@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use serde_json::{Map, Value};
 
 /// Gallery model
 #[derive(Debug, Deserialize)]
@@ -14,4 +15,7 @@ pub struct Gallery {
     pub watcher_count: u64,
     pub image_count: u64,
     pub image_ids: Option<Vec<u64>>,
+    /// Fields unknown to this model, kept by lenient decoding.
+    #[serde(default)]
+    pub extra: Map<String, Value>,
 }
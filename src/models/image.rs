@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use serde_json::{Map, Value};
 
 use super::Id;
 
@@ -14,6 +15,9 @@ pub struct Representations {
     pub large: String,
     pub tall: String,
     pub full: String,
+    /// Fields unknown to this model, kept by lenient decoding.
+    #[serde(default)]
+    pub extra: Map<String, Value>,
 }
 
 /// Image model
@@ -47,4 +51,26 @@ pub struct Image {
     pub is_rendered: bool,
     pub is_optimized: bool,
     pub spoilered: Option<bool>,
+    /// Fields unknown to this model, kept by lenient decoding.
+    #[serde(default)]
+    pub extra: Map<String, Value>,
+}
+
+/// JSON of image with all fields set, for tests.
+#[cfg(test)]
+pub(crate) fn sample(id: u64) -> serde_json::Value {
+    let representations = serde_json::json!({
+        "thumb_tiny": "", "thumb_small": "", "thumb": "", "small": "",
+        "medium": "", "large": "", "tall": "", "full": "",
+    });
+    serde_json::json!({
+        "id": id, "created_at": "2020-01-01T00:00:00Z", "updated_at": "2020-01-01T00:00:00Z",
+        "first_seen_at": "2020-01-01T00:00:00Z", "score": 0, "comment_count": 0, "width": 1,
+        "height": 1, "file_name": "", "description": "", "uploader": "", "uploader_id": null,
+        "image": "", "upvotes": 0, "downvotes": 0, "faves": 0, "tags": "", "tag_ids": [],
+        "aspect_ratio": 1.0, "original_format": "png", "mime_type": "image/png",
+        "sha512_hash": "", "orig_sha512_hash": null, "source_url": "",
+        "representations": representations, "is_rendered": true, "is_optimized": true,
+        "spoilered": null,
+    })
 }
@@ -1,6 +1,11 @@
 //! Derpibooru models.
+//!
+//! Models are strict by default: response missing any field fails to decode. Adapters with
+//! lenient decoding enabled (see [SyncAdapter::lenient](crate::SyncAdapter::lenient)) require
+//! only ids and creation dates, use defaults for other missing fields and keep fields unknown
+//! to a model in its `extra` map.
 mod gallery;
 mod id;
-mod image;
+pub(crate) mod image;
 
 pub use self::{gallery::Gallery, id::Id, image::Image};
@@ -0,0 +1,549 @@
+//! Typed builder of search queries.
+//!
+//! [Query] is a tree of tag terms, field comparisons and boolean operators, which renders
+//! (with `Display`) to a query string in Philomena syntax, with terms escaped as needed.
+//! Queries are combined with [and](Query::and), [or](Query::or) and [not](Query::not) methods
+//! or with `&`, `|` and `!` operators. Query strings are parsed back with [Query::parse],
+//! which reports syntax errors with their position.
+//!
+//! # Example
+//! ```
+//! use chrono::{TimeZone, Utc};
+//! use derpiboorust::{query::{field, tag}, Search};
+//!
+//! let since = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+//! let query = tag("safe")
+//!     & (tag("luna") | tag("princess celestia").boost(2.0))
+//!     & !tag("sad, but true")
+//!     & field("score").gte(100)
+//!     & field("created_at").gte(since);
+//!
+//! assert_eq!(
+//!     query.to_string(),
+//!     "safe, (luna || princess celestia^2), -\"sad, but true\", score.gte:100, created_at.gte:2020-01-01T00:00:00Z"
+//! );
+//!
+//! let query = query.to_string();
+//! let request = Search::new(&query);
+//! ```
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::{convert::TryFrom, fmt, ops, str::FromStr};
+
+mod parse;
+
+pub use parse::{ParseError, ParseErrorKind};
+
+/// Search query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// Tag term.
+    Term(Term),
+    /// Field comparison.
+    Field(Field),
+    /// Matches when all queries match, empty one matches everything.
+    And(Vec<Query>),
+    /// Matches when any query matches.
+    Or(Vec<Query>),
+    /// Matches when query doesn't match.
+    Not(Box<Query>),
+}
+impl Query {
+    /// Parse query string, without sending anything.
+    ///
+    /// Accepts `,`, `&&` and `AND`; `||` and `OR`; `-`, `!` and `NOT` operators, parentheses,
+    /// quoted and escaped terms, `~` fuzziness and `^` boost. Known image fields (`score`,
+    /// `created_at`, ...) are parsed to typed [Field] comparisons, other `namespace:` prefixes
+    /// are part of tags. Partial (`2020-01`) and relative (`3 days ago`) dates are kept as text
+    /// for the server to resolve. Displaying parsed query gives canonical query string.
+    /// ```
+    /// use derpiboorust::query::{ParseErrorKind, Query};
+    ///
+    /// let query = Query::parse("safe AND (luna OR celestia) AND score.gt:10").unwrap();
+    /// assert_eq!(query.to_string(), "safe, (luna || celestia), score.gt:10");
+    ///
+    /// let error = Query::parse("safe, socre.gt:10").unwrap_err();
+    /// assert_eq!(error.kind(), ParseErrorKind::UnknownField);
+    /// assert_eq!(error.span(), 6..11);
+    /// ```
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        parse::Parser::new(input).parse()
+    }
+    /// Query matching both this and `other` query.
+    pub fn and<Q: Into<Query>>(self, other: Q) -> Self {
+        match (self, other.into()) {
+            (Query::And(mut left), Query::And(right)) => {
+                left.extend(right);
+                Query::And(left)
+            }
+            (Query::And(mut left), right) => {
+                left.push(right);
+                Query::And(left)
+            }
+            (left, Query::And(mut right)) => {
+                right.insert(0, left);
+                Query::And(right)
+            }
+            (left, right) => Query::And(vec![left, right]),
+        }
+    }
+    /// Query matching this or `other` query.
+    pub fn or<Q: Into<Query>>(self, other: Q) -> Self {
+        match (self, other.into()) {
+            (Query::Or(mut left), Query::Or(right)) => {
+                left.extend(right);
+                Query::Or(left)
+            }
+            (Query::Or(mut left), right) => {
+                left.push(right);
+                Query::Or(left)
+            }
+            (left, Query::Or(mut right)) => {
+                right.insert(0, left);
+                Query::Or(right)
+            }
+            (left, right) => Query::Or(vec![left, right]),
+        }
+    }
+    /// Query matching when this query doesn't match.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Query::Not(Box::new(self))
+    }
+    /// Binding strength of operator, for deciding where parentheses are needed.
+    fn precedence(&self) -> u8 {
+        match self {
+            Query::Or(queries) if queries.len() > 1 => 0,
+            Query::And(queries) if queries.len() > 1 => 1,
+            _ => 2,
+        }
+    }
+    fn fmt_operand(&self, f: &mut fmt::Formatter, precedence: u8) -> fmt::Result {
+        if self.precedence() < precedence {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (queries, separator) = match self {
+            Query::Term(term) => return term.fmt(f),
+            Query::Field(field) => return field.fmt(f),
+            Query::Not(query) => {
+                f.write_str("-")?;
+                return query.fmt_operand(f, 2);
+            }
+            Query::And(queries) if queries.is_empty() => return f.write_str("*"),
+            Query::Or(queries) if queries.is_empty() => return f.write_str("-*"),
+            Query::And(queries) => (queries, ", "),
+            Query::Or(queries) => (queries, " || "),
+        };
+
+        let precedence = self.precedence();
+        for (index, query) in queries.iter().enumerate() {
+            if index > 0 {
+                f.write_str(separator)?;
+            }
+            // Operands of the same operator are grouped too, to keep grouping of nested queries
+            query.fmt_operand(f, precedence + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, ParseError> {
+        Self::parse(input)
+    }
+}
+impl<Q: Into<Query>> ops::BitAnd<Q> for Query {
+    type Output = Query;
+
+    fn bitand(self, other: Q) -> Query {
+        self.and(other)
+    }
+}
+impl<Q: Into<Query>> ops::BitOr<Q> for Query {
+    type Output = Query;
+
+    fn bitor(self, other: Q) -> Query {
+        self.or(other)
+    }
+}
+impl ops::Not for Query {
+    type Output = Query;
+
+    fn not(self) -> Query {
+        Query::not(self)
+    }
+}
+
+/// Tag term.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    /// Tag name or, for wildcard terms, tag pattern where `\` escapes literal `*`, `?` and `\`.
+    pub tag: String,
+    /// Whether tag is a pattern with wildcards.
+    pub wildcard: bool,
+    /// Fuzziness of matching.
+    pub fuzz: Option<f64>,
+    /// Relevance boost.
+    pub boost: Option<f64>,
+}
+impl Term {
+    /// Match images tagged with `tag`, characters special in queries are escaped.
+    pub fn new(tag: &str) -> Self {
+        Term {
+            tag: tag.to_owned(),
+            wildcard: false,
+            fuzz: None,
+            boost: None,
+        }
+    }
+    /// Match images with tags matching `pattern`, where `*` matches any characters,
+    /// `?` matches any single character and `\` escapes them. Pattern without wildcards
+    /// gives plain tag term.
+    pub fn wildcard(pattern: &str) -> Self {
+        let mut tag = String::new();
+        let mut normalized = String::new();
+        let mut wildcard = false;
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                if let Some(escaped) = chars.next() {
+                    tag.push(escaped);
+                    push_literal(&mut normalized, escaped);
+                }
+                continue;
+            }
+
+            wildcard |= c == '*' || c == '?';
+            tag.push(c);
+            normalized.push(c);
+        }
+
+        if !wildcard {
+            return Term::new(&tag);
+        }
+        Term {
+            wildcard: true,
+            ..Term::new(&normalized)
+        }
+    }
+    /// Match tags similar to term, `fuzz` is either edit distance or similarity from 0 to 1.
+    ///
+    /// # Panics
+    /// Panics if `fuzz` is not finite.
+    pub fn fuzzy(mut self, fuzz: f64) -> Self {
+        assert!(fuzz.is_finite(), "fuzziness must be finite");
+        self.fuzz = Some(fuzz);
+        self
+    }
+    /// Multiply relevance of matching images by `boost`.
+    ///
+    /// # Panics
+    /// Panics if `boost` is not finite.
+    pub fn boost(mut self, boost: f64) -> Self {
+        assert!(boost.is_finite(), "boost must be finite");
+        self.boost = Some(boost);
+        self
+    }
+}
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_term(f, &self.tag, self.wildcard)?;
+        if let Some(fuzz) = self.fuzz {
+            write!(f, "~{}", fuzz)?;
+        }
+        if let Some(boost) = self.boost {
+            write!(f, "^{}", boost)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<Q: Into<Query>> ops::BitAnd<Q> for Term {
+    type Output = Query;
+
+    fn bitand(self, other: Q) -> Query {
+        Query::from(self).and(other)
+    }
+}
+impl<Q: Into<Query>> ops::BitOr<Q> for Term {
+    type Output = Query;
+
+    fn bitor(self, other: Q) -> Query {
+        Query::from(self).or(other)
+    }
+}
+impl ops::Not for Term {
+    type Output = Query;
+
+    fn not(self) -> Query {
+        Query::from(self).not()
+    }
+}
+
+impl From<Term> for Query {
+    fn from(term: Term) -> Self {
+        Query::Term(term)
+    }
+}
+impl From<Field> for Query {
+    fn from(field: Field) -> Self {
+        Query::Field(field)
+    }
+}
+
+/// Comparison of image field with a value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    /// Field name, like `score` or `created_at`.
+    pub name: String,
+    /// Comparison operator.
+    pub comparison: Comparison,
+    /// Compared value.
+    pub value: FieldValue,
+}
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)?;
+        match self.comparison {
+            Comparison::Eq => f.write_str(":")?,
+            Comparison::Gt => f.write_str(".gt:")?,
+            Comparison::Gte => f.write_str(".gte:")?,
+            Comparison::Lt => f.write_str(".lt:")?,
+            Comparison::Lte => f.write_str(".lte:")?,
+        }
+
+        match &self.value {
+            FieldValue::Int(value) => write!(f, "{}", value),
+            FieldValue::UInt(value) => write!(f, "{}", value),
+            FieldValue::Float(value) => write!(f, "{}", value),
+            FieldValue::Date(date) => {
+                f.write_str(&date.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            }
+            FieldValue::Text(text) => write_term(f, text, false),
+        }
+    }
+}
+
+/// Field comparison operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+/// Value compared with field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Int(i64),
+    /// Integer above `i64::MAX`.
+    UInt(u64),
+    Float(f64),
+    Date(DateTime<Utc>),
+    Text(String),
+}
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        FieldValue::Int(value)
+    }
+}
+impl From<u64> for FieldValue {
+    fn from(value: u64) -> Self {
+        match i64::try_from(value) {
+            Ok(value) => FieldValue::Int(value),
+            Err(_) => FieldValue::UInt(value),
+        }
+    }
+}
+impl From<i32> for FieldValue {
+    fn from(value: i32) -> Self {
+        FieldValue::Int(i64::from(value))
+    }
+}
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        FieldValue::Float(value)
+    }
+}
+impl From<DateTime<Utc>> for FieldValue {
+    fn from(value: DateTime<Utc>) -> Self {
+        FieldValue::Date(value)
+    }
+}
+impl<'a> From<&'a str> for FieldValue {
+    fn from(value: &'a str) -> Self {
+        FieldValue::Text(value.to_owned())
+    }
+}
+
+/// Builder of comparisons of a field, created by [field].
+///
+/// # Panics
+/// Comparisons panic if compared value is not finite number.
+#[derive(Debug, Clone)]
+pub struct FieldBuilder {
+    name: String,
+}
+impl FieldBuilder {
+    fn compare<V: Into<FieldValue>>(self, comparison: Comparison, value: V) -> Query {
+        let value = value.into();
+        if let FieldValue::Float(value) = value {
+            assert!(value.is_finite(), "compared number must be finite");
+        }
+
+        Query::Field(Field {
+            name: self.name,
+            comparison,
+            value,
+        })
+    }
+    /// Field equals `value`.
+    pub fn eq<V: Into<FieldValue>>(self, value: V) -> Query {
+        self.compare(Comparison::Eq, value)
+    }
+    /// Field is greater than `value`.
+    pub fn gt<V: Into<FieldValue>>(self, value: V) -> Query {
+        self.compare(Comparison::Gt, value)
+    }
+    /// Field is greater than or equal to `value`.
+    pub fn gte<V: Into<FieldValue>>(self, value: V) -> Query {
+        self.compare(Comparison::Gte, value)
+    }
+    /// Field is less than `value`.
+    pub fn lt<V: Into<FieldValue>>(self, value: V) -> Query {
+        self.compare(Comparison::Lt, value)
+    }
+    /// Field is less than or equal to `value`.
+    pub fn lte<V: Into<FieldValue>>(self, value: V) -> Query {
+        self.compare(Comparison::Lte, value)
+    }
+}
+
+/// Tag term, see [Term::new].
+pub fn tag(tag: &str) -> Term {
+    Term::new(tag)
+}
+
+/// Comparison of field `name`, like `score` or `created_at`.
+pub fn field(name: &str) -> FieldBuilder {
+    FieldBuilder {
+        name: name.to_owned(),
+    }
+}
+
+/// Characters which can't appear in unquoted term.
+const SPECIAL: &[char] = &['(', ')', '"', ',', '\\', '~', '^', '*', '?', '&', '|'];
+
+/// Push literal character to wildcard pattern, escaping it if it's special in patterns.
+fn push_literal(pattern: &mut String, c: char) {
+    if matches!(c, '*' | '?' | '\\') {
+        pattern.push('\\');
+    }
+    pattern.push(c);
+}
+
+/// Write term, quoting it if it contains special characters or could be read as operator
+/// or field comparison. Wildcard patterns keep their escapes.
+fn write_term(f: &mut fmt::Formatter, term: &str, wildcard: bool) -> fmt::Result {
+    let pattern_char = |c: char| wildcard && matches!(c, '*' | '?' | '\\');
+    let special = |c: char| SPECIAL.contains(&c) && !pattern_char(c);
+    let quote = term.is_empty()
+        || term.contains(special)
+        || term.starts_with(['-', '!'])
+        || term.starts_with(char::is_whitespace)
+        || term.ends_with(char::is_whitespace)
+        || term
+            .split_whitespace()
+            .any(|word| ["AND", "OR", "NOT"].contains(&word))
+        || parse::is_field(term);
+
+    if !quote {
+        return f.write_str(term);
+    }
+
+    f.write_str("\"")?;
+    for c in term.chars() {
+        if c == '"' || (matches!(c, '*' | '?' | '\\') && !pattern_char(c)) {
+            f.write_str("\\")?;
+        }
+        write!(f, "{}", c)?;
+    }
+    f.write_str("\"")
+}
+
+#[test]
+fn render() {
+    let query = (tag("a") | tag("b")) & !(tag("c") & tag("d")) & !tag("e");
+    assert_eq!(query.to_string(), "(a || b), -(c, d), -e");
+
+    let query = tag("a") | tag("b") & tag("c");
+    assert_eq!(query.to_string(), "a || b, c");
+
+    let query = Query::Or(vec![
+        tag("a").into(),
+        Query::Or(vec![tag("b").into(), tag("c").into()]),
+    ]);
+    assert_eq!(query.to_string(), "a || (b || c)");
+
+    let query = tag("twilight sparkle").fuzzy(0.8) & Term::wildcard("artist:*").boost(1.5);
+    assert_eq!(query.to_string(), "twilight sparkle~0.8, artist:*^1.5");
+
+    assert_eq!(Query::And(Vec::new()).to_string(), "*");
+    assert_eq!((!tag("a")).to_string(), "-a");
+}
+
+#[test]
+fn escape() {
+    assert_eq!(tag("(safe)").to_string(), r#""(safe)""#);
+    assert_eq!(tag(r#"say "hi""#).to_string(), r#""say \"hi\"""#);
+    assert_eq!(tag("-dash").to_string(), r#""-dash""#);
+    assert_eq!(tag("OR").to_string(), r#""OR""#);
+    assert_eq!(tag("what?").to_string(), r#""what\?""#);
+    assert_eq!(Term::wildcard("what?").to_string(), "what?");
+    assert_eq!(Term::wildcard("a, b*").to_string(), r#""a, b*""#);
+    assert_eq!(Term::wildcard(r"a\*b*").to_string(), r"a\*b*");
+    assert_eq!(Term::wildcard(r"(a\b)?").to_string(), r#""(ab)?""#);
+    assert_eq!(Term::wildcard(r"a\?b"), Term::new("a?b"));
+    assert_eq!(tag("").to_string(), r#""""#);
+    assert_eq!(tag("NOT funny").to_string(), r#""NOT funny""#);
+    assert_eq!(tag("a AND b").to_string(), r#""a AND b""#);
+    assert_eq!(tag("sha512_hash:x").to_string(), r#""sha512_hash:x""#);
+    assert_eq!(tag("ANDROID").to_string(), "ANDROID");
+    assert_eq!(tag("score:5").to_string(), r#""score:5""#);
+    assert_eq!(tag("foo.gte:5").to_string(), r#""foo.gte:5""#);
+    assert_eq!(tag("artist:name").to_string(), "artist:name");
+
+    let query = tag("NOT funny") & tag("a AND b") & tag("score:5");
+    assert_eq!(Query::parse(&query.to_string()).unwrap(), query);
+
+    let query = field("uploader").eq("a, b");
+    assert_eq!(query.to_string(), r#"uploader:"a, b""#);
+    let query = field("score").lt(-5) | field("aspect_ratio").gte(1.5);
+    assert_eq!(query.to_string(), "score.lt:-5 || aspect_ratio.gte:1.5");
+    let query = field("id").gt(u64::MAX);
+    assert_eq!(query.to_string(), "id.gt:18446744073709551615");
+    assert_eq!(Query::parse(&query.to_string()).unwrap(), query);
+}
+
+#[test]
+#[should_panic(expected = "boost must be finite")]
+fn non_finite_boost() {
+    let _ = tag("a").boost(f64::NAN);
+}
+
+#[test]
+#[should_panic(expected = "compared number must be finite")]
+fn non_finite_value() {
+    let _ = field("aspect_ratio").gte(f64::INFINITY);
+}
@@ -0,0 +1,732 @@
+use chrono::DateTime;
+use failure::Fail;
+use std::{fmt, ops::Range};
+
+use super::{push_literal, Comparison, Field, FieldValue, Query, Term};
+use crate::error::{Error, ErrorKind};
+
+/// Type of values of a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    Int,
+    Float,
+    Date,
+    Text,
+}
+
+/// Maximum nesting of groups and negations.
+const MAX_DEPTH: usize = 100;
+
+/// Units of relative dates, like `3 days ago`.
+const DATE_UNITS: &[&str] = &["second", "minute", "hour", "day", "week", "month", "year"];
+
+/// Image fields which can be searched by.
+const FIELDS: &[(&str, FieldType)] = &[
+    ("id", FieldType::Int),
+    ("score", FieldType::Int),
+    ("upvotes", FieldType::Int),
+    ("downvotes", FieldType::Int),
+    ("faves", FieldType::Int),
+    ("comment_count", FieldType::Int),
+    ("width", FieldType::Int),
+    ("height", FieldType::Int),
+    ("pixels", FieldType::Int),
+    ("size", FieldType::Int),
+    ("tag_count", FieldType::Int),
+    ("uploader_id", FieldType::Int),
+    ("gallery_id", FieldType::Int),
+    ("aspect_ratio", FieldType::Float),
+    ("wilson_score", FieldType::Float),
+    ("duration", FieldType::Float),
+    ("created_at", FieldType::Date),
+    ("updated_at", FieldType::Date),
+    ("first_seen_at", FieldType::Date),
+    ("uploader", FieldType::Text),
+    ("faved_by", FieldType::Text),
+    ("description", FieldType::Text),
+    ("source_url", FieldType::Text),
+    ("file_name", FieldType::Text),
+    ("mime_type", FieldType::Text),
+    ("original_format", FieldType::Text),
+    ("sha512_hash", FieldType::Text),
+    ("orig_sha512_hash", FieldType::Text),
+];
+
+/// Kind of query syntax error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// Parenthesis without a pair.
+    UnbalancedParenthesis,
+    /// Quoted term without closing quote.
+    UnterminatedQuote,
+    /// Operator without operand, or empty query.
+    MissingOperand,
+    /// Character which can't appear at its position.
+    UnexpectedCharacter,
+    /// Range comparison of a field which doesn't exist.
+    UnknownField,
+    /// Field value or term modifier which can't be parsed.
+    BadValue,
+    /// Groups or negations nested too deep.
+    TooDeep,
+}
+
+/// Query syntax error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    span: Range<usize>,
+    message: String,
+}
+impl ParseError {
+    fn new(kind: ParseErrorKind, span: Range<usize>, message: String) -> Self {
+        ParseError {
+            kind,
+            span,
+            message,
+        }
+    }
+    /// Error kind.
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+    /// Byte range of query where the error is.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}..{}",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+impl Fail for ParseError {}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::new(ErrorKind::InvalidRequest).with_cause(error)
+    }
+}
+
+/// Recursive descent parser, operators from the weakest: OR, AND, NOT.
+pub(super) struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    /// Number of groups and negations being parsed.
+    depth: usize,
+}
+impl<'a> Parser<'a> {
+    pub(super) fn new(input: &'a str) -> Self {
+        Parser {
+            input,
+            pos: 0,
+            depth: 0,
+        }
+    }
+    pub(super) fn parse(mut self) -> Result<Query, ParseError> {
+        let query = self.parse_or()?;
+        self.skip_whitespace();
+
+        match self.peek() {
+            None => Ok(query),
+            Some(')') => Err(self.error_here(
+                ParseErrorKind::UnbalancedParenthesis,
+                "closing parenthesis without opening one",
+            )),
+            Some(c) => Err(self.error_here(
+                ParseErrorKind::UnexpectedCharacter,
+                &format!("unexpected `{}`", c),
+            )),
+        }
+    }
+    fn parse_or(&mut self) -> Result<Query, ParseError> {
+        let mut queries = vec![self.parse_and()?];
+        loop {
+            self.skip_whitespace();
+            if !(self.eat("||") || self.eat_keyword("OR")) {
+                break;
+            }
+            queries.push(self.parse_and()?);
+        }
+
+        Ok(combine(queries, Query::Or))
+    }
+    fn parse_and(&mut self) -> Result<Query, ParseError> {
+        let mut queries = vec![self.parse_not()?];
+        loop {
+            self.skip_whitespace();
+            if !(self.eat(",") || self.eat("&&") || self.eat_keyword("AND")) {
+                break;
+            }
+            queries.push(self.parse_not()?);
+        }
+
+        Ok(combine(queries, Query::And))
+    }
+    fn parse_not(&mut self) -> Result<Query, ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if self.eat("-") || self.eat("!") || self.eat_keyword("NOT") {
+            self.enter(start)?;
+            let query = self.parse_not()?;
+            self.depth -= 1;
+
+            return Ok(query.not());
+        }
+
+        self.parse_atom()
+    }
+    fn parse_atom(&mut self) -> Result<Query, ParseError> {
+        let start = self.pos;
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                self.enter(start)?;
+                let query = self.parse_or()?;
+                self.depth -= 1;
+                self.skip_whitespace();
+                if !self.eat(")") {
+                    return Err(ParseError::new(
+                        ParseErrorKind::UnbalancedParenthesis,
+                        start..start + 1,
+                        String::from("opening parenthesis without closing one"),
+                    ));
+                }
+
+                Ok(query)
+            }
+            Some('"') => {
+                let (tag, pattern) = self.quoted()?;
+                self.term(tag, pattern)
+            }
+            None | Some(',') | Some(')') | Some('|') | Some('&') => {
+                Err(self.error_here(ParseErrorKind::MissingOperand, "expected term or group"))
+            }
+            Some(_) => match self.field()? {
+                Some(field) => Ok(Query::Field(field)),
+                None => {
+                    let (tag, pattern) = self.bare(true)?;
+                    self.term(tag, pattern)
+                }
+            },
+        }
+    }
+    /// Parse modifiers of term, which is a wildcard term if it has `pattern`.
+    fn term(&mut self, tag: String, pattern: Option<String>) -> Result<Query, ParseError> {
+        let mut term = match pattern {
+            Some(pattern) => Term {
+                wildcard: true,
+                ..Term::new(&pattern)
+            },
+            None => Term::new(&tag),
+        };
+
+        loop {
+            if self.eat("~") {
+                term.fuzz = Some(self.number("fuzziness")?);
+            } else if self.eat("^") {
+                term.boost = Some(self.number("boost")?);
+            } else {
+                return Ok(Query::Term(term));
+            }
+        }
+    }
+    /// Parse field comparison, `None` if input is not a known field or range comparison.
+    fn field(&mut self) -> Result<Option<Field>, ParseError> {
+        let (name, comparison, prefix_len) = match field_prefix(&self.input[self.pos..]) {
+            Some(prefix) => prefix,
+            None => return Ok(None),
+        };
+
+        let name_span = self.pos..self.pos + name.len();
+        let field_type = match field_type(name) {
+            Some(field_type) => field_type,
+            // Not a field but a namespaced tag, like `artist:name`
+            None if comparison == Comparison::Eq => return Ok(None),
+            None => {
+                return Err(ParseError::new(
+                    ParseErrorKind::UnknownField,
+                    name_span,
+                    format!("unknown field `{}`", name),
+                ))
+            }
+        };
+
+        self.pos += prefix_len;
+        let value_start = self.pos;
+        let value = match self.peek() {
+            Some('"') => self.quoted()?.0,
+            _ => self.bare(false)?.0,
+        };
+        let value_span = value_start..self.pos;
+        let bad_value = |expected: &str| {
+            ParseError::new(
+                ParseErrorKind::BadValue,
+                value_span.clone(),
+                format!("field `{}` expects {}", name, expected),
+            )
+        };
+
+        let value = match field_type {
+            FieldType::Int => match (value.parse(), value.parse()) {
+                (Ok(value), _) => FieldValue::Int(value),
+                (_, Ok(value)) => FieldValue::UInt(value),
+                _ => return Err(bad_value("an integer")),
+            },
+            FieldType::Float => match value.parse::<f64>() {
+                Ok(value) if value.is_finite() => FieldValue::Float(value),
+                _ => return Err(bad_value("a number")),
+            },
+            FieldType::Date => parse_date(&value).ok_or_else(|| bad_value("a date"))?,
+            FieldType::Text if comparison != Comparison::Eq => {
+                return Err(bad_value("a value without range comparison"))
+            }
+            FieldType::Text => FieldValue::Text(value),
+        };
+
+        Ok(Some(Field {
+            name: name.to_owned(),
+            comparison,
+            value,
+        }))
+    }
+    /// Parse quoted string, returns unescaped content and, if it has wildcards,
+    /// its wildcard pattern.
+    fn quoted(&mut self) -> Result<(String, Option<String>), ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+
+        let mut text = String::new();
+        let mut pattern = String::new();
+        let mut wildcard = false;
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => {
+                    return Err(ParseError::new(
+                        ParseErrorKind::UnterminatedQuote,
+                        start..self.pos,
+                        String::from("quoted term without closing quote"),
+                    ))
+                }
+            };
+            self.pos += c.len_utf8();
+
+            match c {
+                '"' => return Ok((text, wildcard.then_some(pattern))),
+                '\\' => {
+                    if let Some(escaped) = self.peek() {
+                        self.pos += escaped.len_utf8();
+                        text.push(escaped);
+                        push_literal(&mut pattern, escaped);
+                    }
+                }
+                c => {
+                    wildcard |= c == '*' || c == '?';
+                    text.push(c);
+                    pattern.push(c);
+                }
+            }
+        }
+    }
+    /// Parse unquoted term up to operator or, if `modifiers` is set, term modifier.
+    /// Returns unescaped term and, if it has wildcards, its wildcard pattern.
+    fn bare(&mut self, modifiers: bool) -> Result<(String, Option<String>), ParseError> {
+        let start = self.pos;
+        let mut text = String::new();
+        let mut pattern = String::new();
+        let mut wildcard = false;
+        // Parentheses belonging to the term, like in `luna (mlp)`
+        let mut parentheses = 0;
+
+        while let Some(c) = self.peek() {
+            let rest = &self.input[self.pos..];
+            match c {
+                ',' => break,
+                '&' if rest.starts_with("&&") => break,
+                '|' if rest.starts_with("||") => break,
+                '~' | '^' if modifiers => break,
+                ')' if parentheses == 0 => break,
+                ')' => parentheses -= 1,
+                '(' => parentheses += 1,
+                '*' | '?' => wildcard = true,
+                '\\' => {
+                    self.pos += 1;
+                    if let Some(escaped) = self.peek() {
+                        self.pos += escaped.len_utf8();
+                        text.push(escaped);
+                        push_literal(&mut pattern, escaped);
+                    }
+                    continue;
+                }
+                c if c.is_whitespace() => {
+                    let keyword = rest.trim_start();
+                    if starts_with_keyword(keyword, "AND") || starts_with_keyword(keyword, "OR") {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+
+            text.push(c);
+            pattern.push(c);
+            self.pos += c.len_utf8();
+        }
+
+        let trimmed = text.trim_end();
+        if trimmed.is_empty() {
+            return Err(ParseError::new(
+                ParseErrorKind::MissingOperand,
+                start..self.pos.max(start + 1).min(self.input.len()),
+                String::from("expected term"),
+            ));
+        }
+
+        let pattern = wildcard.then(|| pattern.trim_end().to_owned());
+        Ok((trimmed.to_owned(), pattern))
+    }
+    fn number(&mut self, what: &str) -> Result<f64, ParseError> {
+        let start = self.pos;
+        let rest = &self.input[start..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        self.pos += len;
+
+        match rest[..len].parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(number),
+            _ => Err(ParseError::new(
+                ParseErrorKind::BadValue,
+                start..self.pos,
+                format!("expected number as {}", what),
+            )),
+        }
+    }
+    /// Enter group or negation starting at `start`.
+    fn enter(&mut self, start: usize) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ParseError::new(
+                ParseErrorKind::TooDeep,
+                start..self.pos,
+                format!("query is nested deeper than {} levels", MAX_DEPTH),
+            ));
+        }
+
+        Ok(())
+    }
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+    fn eat(&mut self, token: &str) -> bool {
+        if self.input[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            return true;
+        }
+
+        false
+    }
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if starts_with_keyword(&self.input[self.pos..], keyword) {
+            self.pos += keyword.len();
+            return true;
+        }
+
+        false
+    }
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+    fn error_here(&self, kind: ParseErrorKind, message: &str) -> ParseError {
+        let len = self.peek().map_or(0, char::len_utf8);
+        ParseError::new(kind, self.pos..self.pos + len, message.to_owned())
+    }
+}
+
+/// Field name, comparison and length of `name:` or `name.op:` prefix of `input`.
+/// Names consist of lowercase letters, `_` and, after the first character, digits.
+fn field_prefix(input: &str) -> Option<(&str, Comparison, usize)> {
+    let name_len = input
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_lowercase() || c == '_' || (i > 0 && c.is_ascii_digit())))
+        .map_or(input.len(), |(i, _)| i);
+    let (comparison, prefix_len) = match input[name_len..].split_once(':') {
+        _ if name_len == 0 => return None,
+        Some(("", _)) => (Comparison::Eq, name_len + 1),
+        Some((".gt", _)) => (Comparison::Gt, name_len + 4),
+        Some((".gte", _)) => (Comparison::Gte, name_len + 5),
+        Some((".lt", _)) => (Comparison::Lt, name_len + 4),
+        Some((".lte", _)) => (Comparison::Lte, name_len + 5),
+        _ => return None,
+    };
+
+    Some((&input[..name_len], comparison, prefix_len))
+}
+
+fn field_type(name: &str) -> Option<FieldType> {
+    FIELDS
+        .iter()
+        .find(|(field, _)| *field == name)
+        .map(|(_, field_type)| *field_type)
+}
+
+/// Whether unquoted `term` would be read as field comparison.
+pub(super) fn is_field(term: &str) -> bool {
+    match field_prefix(term) {
+        Some((name, Comparison::Eq, _)) => field_type(name).is_some(),
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Whether `input` starts with `keyword` followed by whitespace or group.
+fn starts_with_keyword(input: &str, keyword: &str) -> bool {
+    match input.strip_prefix(keyword) {
+        Some(rest) => rest.starts_with(|c: char| c.is_whitespace() || c == '(' || c == '"'),
+        None => false,
+    }
+}
+
+fn combine(mut queries: Vec<Query>, operator: fn(Vec<Query>) -> Query) -> Query {
+    if queries.len() == 1 {
+        return queries.remove(0);
+    }
+
+    operator(queries)
+}
+
+/// Parse date: full RFC 3339 time, or partial ISO 8601 (`2020`, `2020-01-02T03:04`)
+/// and relative (`3 days ago`) dates, which are kept as text as the server resolves them.
+fn parse_date(value: &str) -> Option<FieldValue> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(FieldValue::Date(date.to_utc()));
+    }
+
+    if is_partial_date(value) || is_relative_date(value) {
+        return Some(FieldValue::Text(value.to_owned()));
+    }
+
+    None
+}
+
+/// Whether `value` is ISO 8601 date with omitted trailing parts, like `2020-01`.
+fn is_partial_date(value: &str) -> bool {
+    // Offset is allowed only after time
+    let (local, offset) = match value.find('T') {
+        Some(time) => match value[time..].find(['Z', '+', '-']) {
+            Some(offset) => value.split_at(time + offset),
+            None => (value, ""),
+        },
+        None => (value, ""),
+    };
+    let offset = match offset {
+        "" | "Z" => "+00:00",
+        offset => offset,
+    };
+
+    // Complete omitted parts and check the result is a valid time
+    let shape = "dddd-dd-ddTdd:dd:dd";
+    let omitted = "0000-01-01T00:00:00";
+    let complete = matches!(local.len(), 4 | 7 | 10 | 13 | 16 | 19)
+        && local
+            .chars()
+            .zip(shape.chars())
+            .all(|(c, expected)| match expected {
+                'd' => c.is_ascii_digit(),
+                _ => c == expected,
+            });
+
+    complete
+        && DateTime::parse_from_str(
+            &format!("{}{}{}", local, &omitted[local.len()..], offset),
+            "%Y-%m-%dT%H:%M:%S%:z",
+        )
+        .is_ok()
+}
+
+/// Whether `value` is relative date, like `3 days ago`.
+fn is_relative_date(value: &str) -> bool {
+    let words: Vec<_> = value.split_whitespace().collect();
+    match words.as_slice() {
+        [count, unit, "ago"] => {
+            let unit = unit.strip_suffix('s').unwrap_or(unit);
+            count.parse::<u64>().is_ok() && DATE_UNITS.contains(&unit)
+        }
+        _ => false,
+    }
+}
+
+#[test]
+fn parse() {
+    use super::{field, tag};
+    use chrono::{TimeZone, Utc};
+
+    let query = Query::parse(
+        "safe, (luna OR \"princess celestia\"^2) && NOT sad, score.gte:100, created_at.lt:2020-01-02T03:00:00+03:00",
+    )
+    .unwrap();
+    let date = Utc.with_ymd_and_hms(2020, 1, 2, 0, 0, 0).unwrap();
+    let expected = tag("safe")
+        & (tag("luna") | tag("princess celestia").boost(2.0))
+        & !tag("sad")
+        & field("score").gte(100)
+        & field("created_at").lt(date);
+    assert_eq!(query, expected);
+    assert_eq!(
+        query.to_string(),
+        "safe, (luna || princess celestia^2), -sad, score.gte:100, created_at.lt:2020-01-02T00:00:00Z"
+    );
+
+    for date in &[
+        "2020",
+        "2020-01",
+        "2020-01-02",
+        "2020-01-02T03",
+        "2020-01-02T03:04Z",
+        "2020-01-02T03:04-05:00",
+        "3 days ago",
+        "1 year ago",
+    ] {
+        let query = Query::parse(&format!("created_at.gte:{}", date)).unwrap();
+        assert_eq!(query, field("created_at").gte(*date), "{}", date);
+        assert_eq!(Query::parse(&query.to_string()).unwrap(), query);
+    }
+
+    for name in &["sha512_hash", "orig_sha512_hash"] {
+        let query = field(name).eq("abc");
+        assert_eq!(query.to_string(), format!("{}:abc", name));
+        assert_eq!(Query::parse(&query.to_string()).unwrap(), query);
+    }
+
+    let query = Query::parse("artist:name, fluttershy (g5)~1, art*, -!width:10").unwrap();
+    let expected = tag("artist:name")
+        & tag("fluttershy (g5)").fuzzy(1.0)
+        & Term::wildcard("art*")
+        & !!field("width").eq(10);
+    assert_eq!(query, expected);
+
+    let query = Query::parse(r#"a\*b*, a\?b, "c\\d?""#).unwrap();
+    let expected = Term::wildcard(r"a\*b*") & tag("a?b") & Term::wildcard(r"c\\d?");
+    assert_eq!(query, expected);
+    assert_eq!(query.to_string(), r#"a\*b*, "a\?b", c\\d?"#);
+    let pattern = Term::wildcard("ab");
+    assert_eq!(Query::parse(&pattern.to_string()).unwrap(), pattern.into());
+
+    let query = Query::parse(r#"a\, b || (c || d), uploader:"x, y""#).unwrap();
+    let expected = Query::Or(vec![
+        tag("a, b").into(),
+        Query::And(vec![
+            Query::Or(vec![tag("c").into(), tag("d").into()]),
+            field("uploader").eq("x, y"),
+        ]),
+    ]);
+    assert_eq!(query, expected);
+}
+
+#[test]
+fn round_trip() {
+    for input in &[
+        r#""a, b", -(c || "d \"e\""), f~0.5^2, "OR", "what\?", art*"#,
+        "(a || b || c), (d, e) || -f",
+        r#"aspect_ratio.lte:1.5, faved_by:"some one", id:5"#,
+        "sha512_hash:abc, orig_sha512_hash:def, 2d:x",
+        r"a\*b*",
+        r"a\?b",
+        r#""c\\d*" || "e?\*""#,
+    ] {
+        let query = Query::parse(input).unwrap();
+        assert_eq!(
+            Query::parse(&query.to_string()).unwrap(),
+            query,
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn errors() {
+    let error = |input: &str| {
+        let error = Query::parse(input).unwrap_err();
+        (error.kind(), error.span())
+    };
+
+    assert_eq!(
+        error("a, (b || c"),
+        (ParseErrorKind::UnbalancedParenthesis, 3..4)
+    );
+    assert_eq!(
+        error("a, b)"),
+        (ParseErrorKind::UnbalancedParenthesis, 4..5)
+    );
+    assert_eq!(error(r#"a, "b"#), (ParseErrorKind::UnterminatedQuote, 3..5));
+    assert_eq!(error("a, , b"), (ParseErrorKind::MissingOperand, 3..4));
+    assert_eq!(error(""), (ParseErrorKind::MissingOperand, 0..0));
+    assert_eq!(error("a ||"), (ParseErrorKind::MissingOperand, 4..4));
+    assert_eq!(
+        error("safe, scroe.gte:5"),
+        (ParseErrorKind::UnknownField, 6..11)
+    );
+    assert_eq!(error("score.gt:many"), (ParseErrorKind::BadValue, 9..13));
+    assert_eq!(
+        error("created_at.lt:yesterday"),
+        (ParseErrorKind::BadValue, 14..23)
+    );
+    assert_eq!(
+        error("created_at:2020-13"),
+        (ParseErrorKind::BadValue, 11..18)
+    );
+    assert_eq!(
+        error("created_at:2020-01-02+03:00"),
+        (ParseErrorKind::BadValue, 11..27)
+    );
+    assert_eq!(
+        error("created_at:3 fortnights ago"),
+        (ParseErrorKind::BadValue, 11..27)
+    );
+    assert_eq!(error("uploader.gt:a"), (ParseErrorKind::BadValue, 12..13));
+    assert_eq!(
+        error("sha512_hash.gt:abc"),
+        (ParseErrorKind::BadValue, 15..18)
+    );
+    assert_eq!(
+        error("aspect_ratio.gte:inf"),
+        (ParseErrorKind::BadValue, 17..20)
+    );
+    assert_eq!(
+        error("aspect_ratio:NaN"),
+        (ParseErrorKind::BadValue, 13..16)
+    );
+    let boost = format!("a^{}", "9".repeat(400));
+    assert_eq!(error(&boost), (ParseErrorKind::BadValue, 2..402));
+    assert_eq!(error("a~x"), (ParseErrorKind::BadValue, 2..2));
+    assert_eq!(error("a ^ b"), (ParseErrorKind::BadValue, 3..3));
+    assert_eq!(
+        error(r#""a" b"#),
+        (ParseErrorKind::UnexpectedCharacter, 4..5)
+    );
+
+    let message = Query::parse("scroe.gte:5").unwrap_err().to_string();
+    assert_eq!(message, "unknown field `scroe` at 0..5");
+}
+
+#[test]
+fn too_deep() {
+    let deep = |prefix: &str| {
+        let error = Query::parse(&prefix.repeat(100_000)).unwrap_err();
+        (error.kind(), error.span())
+    };
+
+    assert_eq!(deep("("), (ParseErrorKind::TooDeep, 100..101));
+    assert_eq!(deep("-"), (ParseErrorKind::TooDeep, 100..101));
+    assert_eq!(deep("NOT "), (ParseErrorKind::TooDeep, 400..403));
+
+    let nested = format!("{}a{}", "(".repeat(100), ")".repeat(100));
+    assert!(Query::parse(&nested).is_ok());
+}
@@ -0,0 +1,93 @@
+use reqwest::Url;
+use std::fmt;
+
+/// API endpoint, determined by request URL.
+/// ```
+/// use derpiboorust::{request::Endpoint, Gallery, Request};
+///
+/// let url = Gallery::new("Blossomforth", 2683).build().unwrap();
+/// assert_eq!(Endpoint::of(&url), Some(Endpoint::Gallery));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    /// Image search, `/search.json`.
+    Search,
+    /// Image lists, `/lists.json`.
+    Lists,
+    /// Single image, `/{id}.json`.
+    Image,
+    /// Recent images, `/images.json`.
+    Images,
+    /// Images watched by user, `/images/watched.json`.
+    Watched,
+    /// Single gallery, `/galleries/{user}/{id}.json`.
+    Gallery,
+    /// Galleries of user, `/galleries/{user}.json`.
+    Galleries,
+}
+impl Endpoint {
+    /// Determine endpoint of request URL, `None` for unknown URLs.
+    pub fn of(url: &Url) -> Option<Self> {
+        let mut segments = url.path_segments()?.rev().filter(|s| !s.is_empty());
+        let last = segments.next()?;
+        let name = last.strip_suffix(".json")?;
+        let parent = segments.next();
+        let grandparent = segments.next();
+
+        let endpoint = match (name, parent, grandparent) {
+            (_, Some(_), Some("galleries")) => Endpoint::Gallery,
+            (_, Some("galleries"), _) => Endpoint::Galleries,
+            ("watched", Some("images"), _) => Endpoint::Watched,
+            ("search", ..) => Endpoint::Search,
+            ("lists", ..) => Endpoint::Lists,
+            ("images", ..) => Endpoint::Images,
+            (id, ..) if id.parse::<u64>().is_ok() => Endpoint::Image,
+            _ => return None,
+        };
+
+        Some(endpoint)
+    }
+    /// Endpoint name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Endpoint::Search => "search",
+            Endpoint::Lists => "lists",
+            Endpoint::Image => "image",
+            Endpoint::Images => "images",
+            Endpoint::Watched => "watched",
+            Endpoint::Gallery => "gallery",
+            Endpoint::Galleries => "galleries",
+        }
+    }
+}
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[test]
+fn endpoints() {
+    use crate::request::{Galleries, Image, Images, Lists, Request, Search, Watched};
+
+    let endpoint = |url: Result<Url, crate::Error>| Endpoint::of(&url.unwrap());
+
+    assert_eq!(
+        endpoint(Search::new("safe").build()),
+        Some(Endpoint::Search)
+    );
+    assert_eq!(endpoint(Lists::new().build()), Some(Endpoint::Lists));
+    assert_eq!(endpoint(Image::new(1).build()), Some(Endpoint::Image));
+    assert_eq!(endpoint(Images::new().build()), Some(Endpoint::Images));
+    assert_eq!(
+        endpoint(Watched::new("key").build()),
+        Some(Endpoint::Watched)
+    );
+    assert_eq!(
+        endpoint(Galleries::new("user").build()),
+        Some(Endpoint::Galleries)
+    );
+
+    let mirror = Url::parse("https://booru.example.com/mirror/galleries/user/1.json").unwrap();
+    assert_eq!(Endpoint::of(&mirror), Some(Endpoint::Gallery));
+}
@@ -1,7 +1,7 @@
-use failure::Error;
 use reqwest::Url;
 
-use super::{build_url, response::GalleriesResponse, QueryPairs, Request};
+use super::{build_url, response::GalleriesResponse, Paginated, QueryPairs, Request};
+use crate::{models, Error};
 
 /// Request for fetching user galleries (`/galleries/username.json`).
 /// ```
@@ -12,7 +12,7 @@ use super::{build_url, response::GalleriesResponse, QueryPairs, Request};
 ///     .include_images();
 /// ```
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Galleries<'a> {
     username: &'a str,
     query: QueryPairs<'a>,
@@ -39,9 +39,20 @@ impl<'a> Galleries<'a> {
 impl<'a> Request<'a> for Galleries<'a> {
     type ResponseValue = GalleriesResponse;
 
-    fn build(&self) -> Result<Url, Error> {
+    fn build_with_base(&self, base: &Url) -> Result<Url, Error> {
         let galleries_url = format!("galleries/{}.json", self.username);
-        build_url(&galleries_url, &self.query)
+        build_url(base, &galleries_url, &self.query)
+    }
+}
+
+impl<'a> Paginated<'a> for Galleries<'a> {
+    type Item = models::Gallery;
+
+    fn with_page(self, page: u64) -> Self {
+        self.page(page)
+    }
+    fn items(response: GalleriesResponse) -> Vec<Self::Item> {
+        response
     }
 }
 
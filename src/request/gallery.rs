@@ -1,7 +1,7 @@
-use failure::Error;
 use reqwest::Url;
 
-use super::{build_url, response::GalleryResponse, QueryPairs, Request};
+use super::{build_url, response::GalleryResponse, Paginated, QueryPairs, Request};
+use crate::{models, Error};
 
 /// Request for fetching user gallery (`/galleries/username/id.json`).
 /// ```
@@ -10,7 +10,7 @@ use super::{build_url, response::GalleryResponse, QueryPairs, Request};
 /// let request = Gallery::new("Blossomforth", 2683).page(2);
 /// ```
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Gallery<'a> {
     username: &'a str,
     id: u64,
@@ -37,9 +37,20 @@ impl<'a> Gallery<'a> {
 impl<'a> Request<'a> for Gallery<'a> {
     type ResponseValue = GalleryResponse;
 
-    fn build(&self) -> Result<Url, Error> {
+    fn build_with_base(&self, base: &Url) -> Result<Url, Error> {
         let gallery_url = format!("galleries/{}/{}.json", self.username, self.id);
-        build_url(&gallery_url, &self.query)
+        build_url(base, &gallery_url, &self.query)
+    }
+}
+
+impl<'a> Paginated<'a> for Gallery<'a> {
+    type Item = models::Image;
+
+    fn with_page(self, page: u64) -> Self {
+        self.page(page)
+    }
+    fn items(response: GalleryResponse) -> Vec<Self::Item> {
+        response.images
     }
 }
 
@@ -1,7 +1,7 @@
-use failure::Error;
 use reqwest::Url;
 
 use super::{build_url, response::ImageResponse, QueryPairs, Request};
+use crate::Error;
 
 /// Request for fetching single image (`/images/1941825.json`).
 /// ```
@@ -10,7 +10,7 @@ use super::{build_url, response::ImageResponse, QueryPairs, Request};
 /// let request = Image::new(1941825);
 /// ```
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Image {
     id: u64,
 }
@@ -24,11 +24,11 @@ impl Image {
 impl<'a> Request<'a> for Image {
     type ResponseValue = ImageResponse;
 
-    fn build(&self) -> Result<Url, Error> {
+    fn build_with_base(&self, base: &Url) -> Result<Url, Error> {
         let query = QueryPairs::new();
         let image_url = format!("{}.json", self.id);
 
-        build_url(&image_url, &query)
+        build_url(base, &image_url, &query)
     }
 }
 
@@ -1,8 +1,10 @@
-use failure::Error;
 use reqwest::Url;
 
 use super::{Bound, Order};
-use crate::request::{build_url, response::ImagesResponse, QueryPairs, Request};
+use crate::request::{
+    build_url, response::ImagesResponse, Keyset, KeysetPaginated, Paginated, QueryPairs, Request,
+};
+use crate::{models, Error};
 
 /// Request for fetching images (`/images.json`).
 /// ```
@@ -16,7 +18,7 @@ use crate::request::{build_url, response::ImagesResponse, QueryPairs, Request};
 ///     .random();
 /// ```
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Images<'a> {
     query: QueryPairs<'a>,
 }
@@ -60,8 +62,36 @@ impl<'a> Images<'a> {
 impl<'a> Request<'a> for Images<'a> {
     type ResponseValue = ImagesResponse;
 
-    fn build(&self) -> Result<Url, Error> {
-        build_url("images.json", &self.query)
+    fn build_with_base(&self, base: &Url) -> Result<Url, Error> {
+        build_url(base, "images.json", &self.query)
+    }
+}
+
+impl<'a> Paginated<'a> for Images<'a> {
+    type Item = models::Image;
+
+    fn with_page(self, page: u64) -> Self {
+        self.page(page)
+    }
+    fn items(response: ImagesResponse) -> Vec<Self::Item> {
+        response.images
+    }
+}
+
+impl<'a> KeysetPaginated<'a> for Images<'a> {
+    fn keyset(mut self, keyset: Keyset, before: Option<&str>, inclusive: bool) -> Self {
+        // Lower bounds are kept, they limit the walk
+        for key in &["page", "random", "lt", "lte"] {
+            self.query.remove(key);
+        }
+        self.query.insert("constraint", keyset.constraint());
+        self.query.insert("order", Order::Descending);
+        if let Some(before) = before {
+            let bound = if inclusive { "lte" } else { "lt" };
+            self.query.insert(bound, before);
+        }
+
+        self
     }
 }
 
@@ -5,7 +5,7 @@ pub use self::{images::Images, watched::Watched};
 use crate::request::QueryPairValue;
 
 /// Constraint bound.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Bound<'a> {
     Gt(&'a str),
     Gte(&'a str),
@@ -24,7 +24,7 @@ impl<'a> Bound<'a> {
 }
 
 /// Constraint order.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Order {
     Ascending,
     Descending,
@@ -1,8 +1,10 @@
-use failure::Error;
 use reqwest::Url;
 
 use super::{Bound, Order};
-use crate::request::{build_url, response::ImagesResponse, QueryPairs, Request};
+use crate::request::{
+    build_url, response::ImagesResponse, Keyset, KeysetPaginated, Paginated, QueryPairs, Request,
+};
+use crate::{models, Error};
 
 /// Request for fetching user watched images (`/images/watched.json`).
 /// ```
@@ -15,7 +17,7 @@ use crate::request::{build_url, response::ImagesResponse, QueryPairs, Request};
 ///     .random();
 /// ```
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Watched<'a> {
     query: QueryPairs<'a>,
 }
@@ -59,8 +61,36 @@ impl<'a> Watched<'a> {
 impl<'a> Request<'a> for Watched<'a> {
     type ResponseValue = ImagesResponse;
 
-    fn build(&self) -> Result<Url, Error> {
-        build_url("images/watched.json", &self.query)
+    fn build_with_base(&self, base: &Url) -> Result<Url, Error> {
+        build_url(base, "images/watched.json", &self.query)
+    }
+}
+
+impl<'a> Paginated<'a> for Watched<'a> {
+    type Item = models::Image;
+
+    fn with_page(self, page: u64) -> Self {
+        self.page(page)
+    }
+    fn items(response: ImagesResponse) -> Vec<Self::Item> {
+        response.images
+    }
+}
+
+impl<'a> KeysetPaginated<'a> for Watched<'a> {
+    fn keyset(mut self, keyset: Keyset, before: Option<&str>, inclusive: bool) -> Self {
+        // Lower bounds are kept, they limit the walk
+        for key in &["page", "random", "lt", "lte"] {
+            self.query.remove(key);
+        }
+        self.query.insert("constraint", keyset.constraint());
+        self.query.insert("order", Order::Descending);
+        if let Some(before) = before {
+            let bound = if inclusive { "lte" } else { "lt" };
+            self.query.insert(bound, before);
+        }
+
+        self
     }
 }
 
@@ -0,0 +1,42 @@
+use chrono::SecondsFormat;
+
+use super::Paginated;
+use crate::models::Image;
+
+/// Field images are walked by in keyset pagination, from the newest to the oldest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keyset {
+    /// Image id, recommended.
+    Id,
+    /// Image creation time.
+    CreatedAt,
+}
+impl Keyset {
+    /// Constraint name.
+    pub(crate) fn constraint(self) -> &'static str {
+        match self {
+            Keyset::Id => "id",
+            Keyset::CreatedAt => "created_at",
+        }
+    }
+    /// Constraint value of image.
+    pub(crate) fn value(self, image: &Image) -> String {
+        match self {
+            Keyset::Id => image.id.to_string(),
+            Keyset::CreatedAt => image
+                .created_at
+                .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        }
+    }
+}
+
+/// Request of images which can be walked by keyset instead of page numbers.
+///
+/// Implemented for [Images](crate::Images) and [Watched](crate::Watched),
+/// see [SyncAdapter::crawl](crate::SyncAdapter::crawl).
+pub trait KeysetPaginated<'a>: Paginated<'a, Item = Image> {
+    /// Same request of images ordered by `keyset` descending, which `keyset` field is less
+    /// than `before` (or not greater, when `inclusive` is set). Lower bounds of the request
+    /// are kept.
+    fn keyset(self, keyset: Keyset, before: Option<&str>, inclusive: bool) -> Self;
+}
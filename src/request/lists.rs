@@ -1,7 +1,7 @@
-use failure::Error;
 use reqwest::Url;
 
 use super::{build_url, response::ListsResponse, QueryPairs, Request};
+use crate::Error;
 
 /// Request for fetching image lists (`/lists.json`).
 /// ```
@@ -12,7 +12,7 @@ use super::{build_url, response::ListsResponse, QueryPairs, Request};
 ///     .last("2d");
 /// ```
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Lists<'a> {
     query: QueryPairs<'a>,
 }
@@ -39,8 +39,8 @@ impl<'a> Lists<'a> {
 impl<'a> Request<'a> for Lists<'a> {
     type ResponseValue = ListsResponse;
 
-    fn build(&self) -> Result<Url, Error> {
-        build_url("lists.json", &self.query)
+    fn build_with_base(&self, base: &Url) -> Result<Url, Error> {
+        build_url(base, "lists.json", &self.query)
     }
 }
 
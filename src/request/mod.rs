@@ -1,31 +1,48 @@
 //! API methods.
-use failure::Error;
 use reqwest::Url;
 use serde::Deserialize;
 
+use crate::Error;
+
+mod endpoint;
 mod galleries;
 mod gallery;
 mod image;
 mod image_list;
+mod keyset;
 mod lists;
+mod paginated;
 pub mod response;
 mod search;
 pub use self::{
+    endpoint::Endpoint,
     galleries::Galleries,
     gallery::Gallery,
     image::Image,
     image_list::{Bound, Images, Order, Watched},
+    keyset::{Keyset, KeysetPaginated},
     lists::Lists,
+    paginated::Paginated,
     search::Search,
 };
 
-static DERPIBOORU_API_BASE: &str = "https://derpibooru.org";
+/// Default API base, used when no other base URL is configured.
+pub static DERPIBOORU_API_BASE: &str = "https://derpibooru.org";
 
 /// Base trait for requests.
 pub trait Request<'de> {
     type ResponseValue: Deserialize<'de>;
 
-    fn build(&self) -> Result<Url, Error>;
+    /// Build request URL relative to the given API base (any Philomena instance).
+    fn build_with_base(&self, base: &Url) -> Result<Url, Error>;
+    /// Build request URL relative to [DERPIBOORU_API_BASE].
+    fn build(&self) -> Result<Url, Error> {
+        self.build_with_base(&default_base())
+    }
+}
+
+pub(crate) fn default_base() -> Url {
+    Url::parse(DERPIBOORU_API_BASE).expect("default API base is a valid URL")
 }
 
 trait QueryPairValue {
@@ -40,7 +57,7 @@ where
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct QueryPairs<'a> {
     pairs: Vec<(&'a str, String)>,
 }
@@ -50,19 +67,59 @@ impl<'a> QueryPairs<'a> {
 
         QueryPairs { pairs }
     }
+    /// Set parameter, replacing previous value of the same key.
     fn insert<V: QueryPairValue>(&mut self, key: &'a str, value: V) {
-        self.pairs.push((key, value.to_query()));
+        let value = value.to_query();
+        match self.pairs.iter_mut().find(|(pair_key, _)| *pair_key == key) {
+            Some(pair) => pair.1 = value,
+            None => self.pairs.push((key, value)),
+        }
+    }
+    fn remove(&mut self, key: &str) {
+        self.pairs.retain(|(pair_key, _)| *pair_key != key);
     }
     fn is_empty(&self) -> bool {
         self.pairs.is_empty()
     }
 }
 
-fn build_url<'a>(path: &str, query: &QueryPairs<'a>) -> Result<Url, Error> {
-    let mut url = Url::parse(DERPIBOORU_API_BASE)?.join(path)?;
+fn build_url<'a>(base: &Url, path: &str, query: &QueryPairs<'a>) -> Result<Url, Error> {
+    // Without trailing slash `join` would replace the last segment of base path
+    let mut url = if base.path().ends_with('/') {
+        base.join(path)?
+    } else {
+        let mut base = base.clone();
+        base.set_path(&format!("{}/", base.path()));
+        base.join(path)?
+    };
     if !query.is_empty() {
         url.query_pairs_mut().extend_pairs(query.pairs.iter());
     }
 
     Ok(url)
 }
+
+#[test]
+fn custom_base() {
+    let mut query = QueryPairs::new();
+    query.insert("page", 2);
+
+    let base = Url::parse("https://booru.example.com/mirror").unwrap();
+    let url = build_url(&base, "search.json", &query).unwrap();
+    let expected = Url::parse("https://booru.example.com/mirror/search.json?page=2").unwrap();
+
+    assert_eq!(url, expected);
+}
+
+#[test]
+fn replace_pair() {
+    let mut query = QueryPairs::new();
+    query.insert("q", "safe");
+    query.insert("page", 2);
+    query.insert("page", 3);
+
+    assert_eq!(
+        query.pairs,
+        vec![("q", String::from("safe")), ("page", String::from("3"))]
+    );
+}
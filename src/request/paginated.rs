@@ -0,0 +1,21 @@
+use super::Request;
+
+/// Request which results are split into pages.
+///
+/// Implemented for [Search](crate::Search), [Images](crate::Images),
+/// [Watched](crate::Watched), [Gallery](crate::Gallery) and [Galleries](crate::Galleries),
+/// see [SyncAdapter::pages](crate::SyncAdapter::pages) and
+/// [AsyncAdapter::pages](crate::AsyncAdapter::pages).
+pub trait Paginated<'a>: Request<'a> + Clone {
+    /// Type of items on a page.
+    type Item;
+
+    /// Same request for the given page (starting from 1).
+    fn with_page(self, page: u64) -> Self;
+    /// Items of a page.
+    fn items(response: Self::ResponseValue) -> Vec<Self::Item>;
+    /// Total number of items, if the response reports it.
+    fn total(_response: &Self::ResponseValue) -> Option<u64> {
+        None
+    }
+}
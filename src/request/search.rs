@@ -1,7 +1,7 @@
-use failure::Error;
 use reqwest::Url;
 
-use super::{build_url, response::SearchResponse, QueryPairs, Request};
+use super::{build_url, response::SearchResponse, Paginated, QueryPairs, Request};
+use crate::{models, Error};
 
 /// Request for searching images (`/search.json`).
 /// ```
@@ -14,7 +14,7 @@ use super::{build_url, response::SearchResponse, QueryPairs, Request};
 ///     .perpage(10);
 /// ```
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Search<'a> {
     query: QueryPairs<'a>,
 }
@@ -51,13 +51,32 @@ impl<'a> Search<'a> {
         self.query.insert("key", key);
         self
     }
+    /// Filter used for the search instead of user's current filter.
+    pub fn filter_id(mut self, id: u64) -> Self {
+        self.query.insert("filter_id", id);
+        self
+    }
 }
 
 impl<'a> Request<'a> for Search<'a> {
     type ResponseValue = SearchResponse;
 
-    fn build(&self) -> Result<Url, Error> {
-        build_url("search.json", &self.query)
+    fn build_with_base(&self, base: &Url) -> Result<Url, Error> {
+        build_url(base, "search.json", &self.query)
+    }
+}
+
+impl<'a> Paginated<'a> for Search<'a> {
+    type Item = models::Image;
+
+    fn with_page(self, page: u64) -> Self {
+        self.page(page)
+    }
+    fn items(response: SearchResponse) -> Vec<Self::Item> {
+        response.search
+    }
+    fn total(response: &SearchResponse) -> Option<u64> {
+        Some(response.total)
     }
 }
 
@@ -69,6 +88,7 @@ fn request() {
         .max_score(322)
         .perpage(5)
         .key("qwezxc123")
+        .filter_id(56027)
         .build()
         .unwrap();
 
@@ -81,6 +101,7 @@ fn request() {
             ("max_score", "322"),
             ("perpage", "5"),
             ("key", "qwezxc123"),
+            ("filter_id", "56027"),
         ],
     )
     .unwrap();
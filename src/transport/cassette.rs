@@ -0,0 +1,228 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use super::{
+    invalid, io_error, AsyncTransport, HttpRequest, HttpResponse, MemoryTransport, ResponseFuture,
+    StoredResponse, Transport,
+};
+use crate::error::Error;
+
+/// Cassette file contents.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+impl Cassette {
+    fn load(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path).map_err(io_error)?;
+
+        serde_json::from_reader(BufReader::new(file)).map_err(invalid)
+    }
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        let file = File::create(path).map_err(io_error)?;
+
+        serde_json::to_writer_pretty(BufWriter::new(file), self).map_err(io_error)
+    }
+}
+
+/// Recorded request and response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    url: String,
+    #[serde(flatten)]
+    response: StoredResponse,
+}
+impl Interaction {
+    fn new(url: &Url, response: &HttpResponse) -> Self {
+        Interaction {
+            url: url.to_string(),
+            response: StoredResponse::from(response),
+        }
+    }
+    fn into_response(self) -> Result<(Url, HttpResponse), Error> {
+        let url = Url::parse(&self.url).map_err(invalid)?;
+
+        Ok((url, self.response.into_response()?))
+    }
+}
+
+/// Transport which records every response of the inner transport to a cassette file.
+///
+/// The file is rewritten after each request, so it is complete even if the process
+/// is interrupted. User key is redacted from recorded URLs, so cassettes can be committed.
+/// Recorded cassette can be served back by [ReplayTransport].
+/// ```no_run
+/// use reqwest::blocking::Client;
+/// use derpiboorust::{transport::RecordingTransport, Image, SyncAdapter};
+///
+/// let transport = RecordingTransport::new(Client::new(), "tests/fixtures/image.json");
+/// let adapter = SyncAdapter::with_transport(transport);
+/// let image = adapter.send(Image::new(1)).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    recorder: Recorder,
+}
+impl<T> RecordingTransport<T> {
+    /// Create new recording transport, existing cassette at `path` is overwritten.
+    pub fn new<P: Into<PathBuf>>(inner: T, path: P) -> Self {
+        let recorder = Recorder {
+            path: path.into(),
+            cassette: Arc::new(Mutex::new(Cassette::default())),
+        };
+
+        RecordingTransport { inner, recorder }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Recorder {
+    path: PathBuf,
+    cassette: Arc<Mutex<Cassette>>,
+}
+impl Recorder {
+    fn record(&self, url: &Url, response: &HttpResponse) -> Result<(), Error> {
+        let mut cassette = self
+            .cassette
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        cassette.interactions.push(Interaction::new(url, response));
+
+        cassette.save(&self.path)
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn execute(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        let url = request.clone().masked().url;
+        let response = self.inner.execute(request)?;
+        self.recorder.record(&url, &response)?;
+
+        Ok(response)
+    }
+}
+
+impl<T: AsyncTransport + Sync> AsyncTransport for RecordingTransport<T> {
+    fn execute(&self, request: HttpRequest) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let url = request.clone().masked().url;
+            let response = self.inner.execute(request).await?;
+            self.recorder.record(&url, &response)?;
+
+            Ok(response)
+        })
+    }
+}
+
+/// Transport which serves responses from a cassette recorded by [RecordingTransport].
+///
+/// Responses for the same URL are served in recording order (the last one is served
+/// repeatedly), request to URL missing in the cassette fails with
+/// [Transport](crate::ErrorKind::Transport) error, no network is used. User key is ignored
+/// when matching URLs, as it's redacted in recorded ones.
+/// ```no_run
+/// use derpiboorust::{transport::ReplayTransport, Image, SyncAdapter};
+///
+/// let transport = ReplayTransport::open("tests/fixtures/image.json").unwrap();
+/// let adapter = SyncAdapter::with_transport(transport);
+/// let image = adapter.send(Image::new(1)).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReplayTransport {
+    responses: MemoryTransport,
+}
+impl ReplayTransport {
+    /// Load cassette from file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let cassette = Cassette::load(path.as_ref())?;
+        let responses = MemoryTransport::new();
+
+        for interaction in cassette.interactions {
+            let (url, response) = interaction.into_response()?;
+            responses.respond(url, response);
+        }
+
+        Ok(ReplayTransport { responses })
+    }
+    /// Requests served so far, with user key redacted.
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.responses.requests()
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn execute(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        Transport::execute(&self.responses, request.masked())
+    }
+}
+
+impl AsyncTransport for ReplayTransport {
+    fn execute(&self, request: HttpRequest) -> ResponseFuture<'_> {
+        AsyncTransport::execute(&self.responses, request.masked())
+    }
+}
+
+#[test]
+fn record_and_replay() {
+    use reqwest::{
+        header::{HeaderValue, ETAG},
+        StatusCode,
+    };
+
+    let path =
+        std::env::temp_dir().join(format!("derpiboorust-cassette-{}.json", std::process::id()));
+    let url = Url::parse("https://derpibooru.org/1.json").unwrap();
+    let mut response = HttpResponse::new(StatusCode::OK, r#"{"id":1}"#);
+    response
+        .headers
+        .insert(ETAG, HeaderValue::from_static("\"abc\""));
+
+    let memory = MemoryTransport::new();
+    memory.respond(url.clone(), response);
+    let recording = RecordingTransport::new(memory, &path);
+    Transport::execute(&recording, HttpRequest::get(url.clone())).unwrap();
+
+    let replay = ReplayTransport::open(&path).unwrap();
+    let replayed = Transport::execute(&replay, HttpRequest::get(url)).unwrap();
+    assert_eq!(replayed.status, StatusCode::OK);
+    assert_eq!(replayed.body, br#"{"id":1}"#);
+    assert_eq!(replayed.headers[ETAG], "\"abc\"");
+
+    let unknown = Url::parse("https://derpibooru.org/2.json").unwrap();
+    assert!(Transport::execute(&replay, HttpRequest::get(unknown)).is_err());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn redact_key() {
+    use reqwest::StatusCode;
+
+    let path = std::env::temp_dir().join(format!(
+        "derpiboorust-cassette-key-{}.json",
+        std::process::id()
+    ));
+    let url = Url::parse("https://derpibooru.org/1.json?key=secret").unwrap();
+
+    let memory = MemoryTransport::new();
+    memory.respond(url.clone(), HttpResponse::new(StatusCode::OK, "{}"));
+    let recording = RecordingTransport::new(memory, &path);
+    Transport::execute(&recording, HttpRequest::get(url.clone())).unwrap();
+
+    let cassette = std::fs::read_to_string(&path).unwrap();
+    assert!(!cassette.contains("secret"));
+
+    let replay = ReplayTransport::open(&path).unwrap();
+    let other_key = Url::parse("https://derpibooru.org/1.json?key=other").unwrap();
+    Transport::execute(&replay, HttpRequest::get(url)).unwrap();
+    Transport::execute(&replay, HttpRequest::get(other_key)).unwrap();
+
+    std::fs::remove_file(path).unwrap();
+}
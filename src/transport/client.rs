@@ -0,0 +1,56 @@
+use reqwest::{blocking, Client};
+
+use super::{AsyncTransport, HttpRequest, HttpResponse, ResponseFuture, Transport};
+use crate::error::Error;
+
+impl Transport for blocking::Client {
+    fn execute(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        let url = request.url.clone();
+        let transport_error = |error| Error::from(error).with_url(url.clone());
+
+        let mut builder = self
+            .request(request.method, request.url)
+            .headers(request.headers);
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        let mut response = builder.send().map_err(transport_error)?;
+
+        let mut body = Vec::new();
+        response.copy_to(&mut body).map_err(transport_error)?;
+
+        Ok(HttpResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body,
+        })
+    }
+}
+
+impl AsyncTransport for Client {
+    fn execute(&self, request: HttpRequest) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let url = request.url.clone();
+            let transport_error = |error| Error::from(error).with_url(url.clone());
+
+            let mut builder = self
+                .request(request.method, request.url)
+                .headers(request.headers);
+            if let Some(timeout) = request.timeout {
+                builder = builder.timeout(timeout);
+            }
+
+            let response = builder.send().await.map_err(transport_error)?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.bytes().await.map_err(transport_error)?;
+
+            Ok(HttpResponse {
+                status,
+                headers,
+                body: body.to_vec(),
+            })
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use failure::err_msg;
+use futures::future;
+use reqwest::Url;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use super::{AsyncTransport, HttpRequest, HttpResponse, ResponseFuture, Transport};
+use crate::error::{Error, ErrorKind};
+
+/// In-memory transport serving prepared responses.
+///
+/// Responses are registered per URL and served in registration order, the last registered
+/// response of URL is served repeatedly. Request to URL without responses fails with
+/// [Transport](crate::ErrorKind::Transport) error. All executed requests are recorded.
+///
+/// Transport is cheaply cloneable and clones share responses and recorded requests,
+/// so a clone can be kept for inspection after moving the transport into an adapter.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTransport {
+    state: Arc<Mutex<State>>,
+}
+#[derive(Debug, Default)]
+struct State {
+    responses: HashMap<Url, VecDeque<HttpResponse>>,
+    requests: Vec<HttpRequest>,
+}
+impl MemoryTransport {
+    /// Create new transport without responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Register response for URL.
+    pub fn respond(&self, url: Url, response: HttpResponse) {
+        self.state()
+            .responses
+            .entry(url)
+            .or_default()
+            .push_back(response);
+    }
+    /// Requests executed so far.
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.state().requests.clone()
+    }
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+    fn serve(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        let mut state = self.state();
+        let url = request.url.clone();
+        state.requests.push(request);
+
+        let queue = match state.responses.get_mut(&url) {
+            Some(queue) if !queue.is_empty() => queue,
+            _ => {
+                let cause = err_msg("no response registered for URL");
+                return Err(Error::new(ErrorKind::Transport)
+                    .with_url(url)
+                    .with_cause(cause));
+            }
+        };
+
+        let response = if queue.len() > 1 {
+            queue.pop_front()
+        } else {
+            queue.front().cloned()
+        };
+
+        Ok(response.expect("queue is not empty"))
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn execute(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        self.serve(request)
+    }
+}
+
+impl AsyncTransport for MemoryTransport {
+    fn execute(&self, request: HttpRequest) -> ResponseFuture<'_> {
+        Box::pin(future::ready(self.serve(request)))
+    }
+}
+
+#[test]
+fn responses() {
+    use reqwest::StatusCode;
+
+    let transport = MemoryTransport::new();
+    let url = Url::parse("https://derpibooru.org/1.json").unwrap();
+    transport.respond(url.clone(), HttpResponse::new(StatusCode::BAD_GATEWAY, ""));
+    transport.respond(url.clone(), HttpResponse::new(StatusCode::OK, "{}"));
+
+    let serve = || Transport::execute(&transport, HttpRequest::get(url.clone()));
+    assert_eq!(serve().unwrap().status, StatusCode::BAD_GATEWAY);
+    assert_eq!(serve().unwrap().status, StatusCode::OK);
+    assert_eq!(serve().unwrap().status, StatusCode::OK);
+    assert_eq!(transport.requests().len(), 3);
+
+    let unknown = Url::parse("https://derpibooru.org/2.json").unwrap();
+    let error = Transport::execute(&transport, HttpRequest::get(unknown)).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Transport);
+}
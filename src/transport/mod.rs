@@ -0,0 +1,258 @@
+//! HTTP transports used by adapters.
+//!
+//! Adapters don't depend on a concrete HTTP client, they send requests through a [Transport]
+//! (sync) or an [AsyncTransport] (async). Both traits are implemented for reqwest's clients,
+//! which are used by default, and for [MemoryTransport], which serves prepared responses
+//! and is useful in tests.
+//!
+//! Real API responses can be recorded to a cassette file with [RecordingTransport]
+//! and served back without network by [ReplayTransport].
+//!
+//! # Example
+//! ```
+//! use reqwest::StatusCode;
+//! use derpiboorust::{
+//!     transport::{HttpResponse, MemoryTransport},
+//!     Image, Request, SyncAdapter,
+//! };
+//!
+//! let transport = MemoryTransport::new();
+//! let url = Image::new(1).build().unwrap();
+//! transport.respond(url, HttpResponse::new(StatusCode::NOT_FOUND, r#"{"error":"Not found"}"#));
+//!
+//! let adapter = SyncAdapter::with_transport(transport);
+//! let error = adapter.send(Image::new(1)).unwrap_err();
+//! assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+//! ```
+use failure::Fail;
+use reqwest::{
+    header::{
+        HeaderMap, HeaderName, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    },
+    Method, StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, future::Future, pin::Pin, time::Duration};
+
+use crate::{
+    adapter::redact,
+    error::{Error, ErrorKind},
+};
+
+mod cassette;
+mod client;
+mod memory;
+
+pub use self::{
+    cassette::{RecordingTransport, ReplayTransport},
+    memory::MemoryTransport,
+};
+
+/// HTTP request.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    /// Time limit of the request, transports should fail with
+    /// [Timeout](crate::ErrorKind::Timeout) error when it's exceeded.
+    pub timeout: Option<Duration>,
+}
+impl HttpRequest {
+    /// Create new `GET` request without headers.
+    pub fn get(url: Url) -> Self {
+        HttpRequest {
+            method: Method::GET,
+            url,
+            headers: HeaderMap::new(),
+            timeout: None,
+        }
+    }
+    /// Replace user key in URL with `REDACTED`, so request can be shared safely.
+    pub fn masked(mut self) -> Self {
+        self.url = redact(&self.url);
+        self
+    }
+    /// Render request as curl command.
+    /// ```
+    /// use derpiboorust::{Image, SyncAdapter};
+    ///
+    /// let adapter = SyncAdapter::new().key("secret");
+    /// let request = adapter.prepare(Image::new(1)).unwrap();
+    /// assert_eq!(
+    ///     request.masked().to_curl(),
+    ///     "curl 'https://derpibooru.org/1.json?key=REDACTED'"
+    /// );
+    /// ```
+    pub fn to_curl(&self) -> String {
+        let mut command = String::from("curl");
+        if self.method != Method::GET {
+            command.push_str(" -X ");
+            command.push_str(self.method.as_str());
+        }
+        command.push(' ');
+        command.push_str(&shell_quote(self.url.as_str()));
+
+        for (name, value) in &self.headers {
+            let header = format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes()));
+            command.push_str(" \\\n  -H ");
+            command.push_str(&shell_quote(&header));
+        }
+
+        command
+    }
+    /// Render request as raw HTTP/1.1 request text.
+    pub fn to_http(&self) -> String {
+        let mut target = self.url.path().to_owned();
+        if let Some(query) = self.url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+
+        let mut text = format!("{} {} HTTP/1.1\r\n", self.method, target);
+        if let Some(host) = self.url.host_str() {
+            match self.url.port() {
+                Some(port) => text.push_str(&format!("host: {}:{}\r\n", host, port)),
+                None => text.push_str(&format!("host: {}\r\n", host)),
+            }
+        }
+        for (name, value) in &self.headers {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            text.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        text.push_str("\r\n");
+
+        text
+    }
+}
+
+/// Quote string for POSIX shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// HTTP response.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+impl HttpResponse {
+    /// Create new response without headers.
+    pub fn new<B: Into<Vec<u8>>>(status: StatusCode, body: B) -> Self {
+        HttpResponse {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+    /// Response body as text, invalid UTF-8 sequences are replaced.
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+}
+
+/// Synchronous HTTP transport.
+pub trait Transport {
+    /// Perform request. Error responses (4xx, 5xx) must be returned as `Ok`.
+    fn execute(&self, request: HttpRequest) -> Result<HttpResponse, Error>;
+}
+
+/// Future returned by [AsyncTransport].
+pub type ResponseFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<HttpResponse, Error>> + Send + 'a>>;
+
+/// Asynchronous HTTP transport.
+pub trait AsyncTransport {
+    /// Perform request. Error responses (4xx, 5xx) must be resolved as `Ok`.
+    fn execute(&self, request: HttpRequest) -> ResponseFuture<'_>;
+}
+
+/// Serializable form of [HttpResponse].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct StoredResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+impl StoredResponse {
+    pub(crate) fn into_response(self) -> Result<HttpResponse, Error> {
+        let status = StatusCode::from_u16(self.status).map_err(invalid)?;
+        let mut response = HttpResponse::new(status, self.body);
+
+        for (name, value) in self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(invalid)?;
+            let value = HeaderValue::from_str(&value).map_err(invalid)?;
+            response.headers.append(name, value);
+        }
+
+        Ok(response)
+    }
+}
+impl<'a> From<&'a HttpResponse> for StoredResponse {
+    fn from(response: &'a HttpResponse) -> Self {
+        let headers = response
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+            .collect();
+
+        StoredResponse {
+            status: response.status.as_u16(),
+            headers,
+            body: response.text().into_owned(),
+        }
+    }
+}
+
+/// Add `If-None-Match` and `If-Modified-Since` headers to request using validators of
+/// previous response, `false` if response has no validators or request has conditional headers.
+pub(crate) fn set_validators(request: &mut HttpRequest, previous: &HttpResponse) -> bool {
+    let headers = &mut request.headers;
+    if headers.contains_key(IF_NONE_MATCH) || headers.contains_key(IF_MODIFIED_SINCE) {
+        return false;
+    }
+
+    let mut set = false;
+    if let Some(etag) = previous.headers.get(ETAG) {
+        headers.insert(IF_NONE_MATCH, etag.clone());
+        set = true;
+    }
+    if let Some(last_modified) = previous.headers.get(LAST_MODIFIED) {
+        headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        set = true;
+    }
+
+    set
+}
+
+pub(crate) fn invalid<E: Fail>(cause: E) -> Error {
+    Error::new(ErrorKind::Decode).with_cause(cause)
+}
+
+pub(crate) fn io_error<E: Fail>(cause: E) -> Error {
+    Error::new(ErrorKind::Io).with_cause(cause)
+}
+
+#[test]
+fn render() {
+    use reqwest::header::USER_AGENT;
+
+    let url = Url::parse("http://localhost:4000/search.json?q=safe&key=secret").unwrap();
+    let mut request = HttpRequest::get(url);
+    request
+        .headers
+        .insert(USER_AGENT, HeaderValue::from_static("it's"));
+
+    assert_eq!(
+        request.to_curl(),
+        "curl 'http://localhost:4000/search.json?q=safe&key=secret' \\\n  -H 'user-agent: it'\\''s'"
+    );
+
+    let request = request.masked();
+    assert_eq!(
+        request.to_http(),
+        "GET /search.json?q=safe&key=REDACTED HTTP/1.1\r\nhost: localhost:4000\r\nuser-agent: it's\r\n\r\n"
+    );
+}